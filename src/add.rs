@@ -0,0 +1,138 @@
+use crate::{absolute_path, discover, git_binary};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use gix::bstr::ByteSlice;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(clap::Args)]
+pub struct AddArgs {
+    url: String,
+    path: Option<Utf8PathBuf>,
+    /// Branch to track in the submodule
+    #[clap(long, short)]
+    branch: Option<String>,
+    /// Clean up what's left of a submodule previously removed from the
+    /// same path before adding it again
+    #[clap(long, short)]
+    force: bool,
+}
+
+// Removes the git directory, config section and empty directory that a
+// submodule removed from the same path can leave behind, which would
+// otherwise make `git submodule add` refuse or reuse the old clone. Nothing
+// is removed if the path is still a submodule.
+fn remove_stale(
+    git: &Path,
+    repo: &gix::Repository,
+    cwd: &Utf8Path,
+    args: &AddArgs,
+) -> Result<(), anyhow::Error> {
+    // Like git, default to the last component of the URL, and name the
+    // submodule after its path.
+    let path = match &args.path {
+        Some(path) => path.clone(),
+        None => match args
+            .url
+            .trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .map(|name| name.trim_end_matches(".git"))
+        {
+            Some(name) if !name.is_empty() => Utf8PathBuf::from(name),
+            _ => anyhow::bail!("cannot determine a path from {}", args.url),
+        },
+    };
+    let Some(work_dir) = repo.work_dir() else {
+        anyhow::bail!("cannot add submodules to a bare repository");
+    };
+    let work_dir = absolute_path(work_dir)?;
+    let abs_path = absolute_path(cwd.join(&path).as_std_path())?;
+    let Ok(name) = abs_path.strip_prefix(&work_dir) else {
+        anyhow::bail!("{} is outside the repository", path);
+    };
+    let name = name.to_string_lossy().replace('\\', "/");
+
+    // Only what a removed submodule left behind is stale. A submodule that's
+    // still there may have commits that exist nowhere but its git directory.
+    let in_index = repo
+        .index_or_empty()?
+        .entry_by_path(name.as_bytes().as_bstr())
+        .is_some_and(|entry| entry.mode == gix::index::entry::Mode::COMMIT);
+    let mut in_gitmodules = false;
+    for submodule in repo.submodules()?.into_iter().flatten() {
+        if submodule.name() == name.as_bytes() || *submodule.path()? == name.as_bytes() {
+            in_gitmodules = true;
+        }
+    }
+    if in_index || in_gitmodules {
+        anyhow::bail!(
+            "{} is still a submodule; remove it with `gm rm` first",
+            path
+        );
+    }
+
+    let git_dir = repo.common_dir().join("modules").join(&name);
+    if git_dir.exists() {
+        std::fs::remove_dir_all(&git_dir)?;
+        println!(
+            "{} {}",
+            "removed stale git directory".bold(),
+            git_dir.display().to_string().dimmed()
+        );
+    }
+    let removed_config = Command::new(git)
+        .arg("config")
+        .arg("--remove-section")
+        .arg(format!("submodule.{}", name))
+        .current_dir(&work_dir)
+        .stderr(std::process::Stdio::null())
+        .status()?
+        .success();
+    if removed_config {
+        println!(
+            "{} {}",
+            "removed stale config".bold(),
+            format!("submodule.{}", name).dimmed()
+        );
+    }
+    if abs_path.is_dir() && std::fs::read_dir(&abs_path)?.next().is_none() {
+        std::fs::remove_dir(&abs_path)?;
+        println!(
+            "{} {}",
+            "removed empty directory".bold(),
+            path.as_str().dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+pub fn add(cwd: &Utf8Path, args: AddArgs) -> Result<(), anyhow::Error> {
+    let git = git_binary("`gm add`")?;
+    let repo = discover(cwd)?;
+
+    if args.force {
+        remove_stale(&git, &repo, cwd, &args)?;
+    }
+
+    let mut command = Command::new(&git);
+    command.arg("submodule").arg("add").current_dir(cwd);
+    if args.force {
+        command.arg("--force");
+    }
+    if let Some(branch) = &args.branch {
+        command.arg("--branch").arg(branch);
+    }
+    command.arg("--").arg(&args.url);
+    if let Some(path) = &args.path {
+        command.arg(path);
+    }
+
+    let status = command.spawn()?.wait()?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
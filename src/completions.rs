@@ -0,0 +1,74 @@
+use crate::{absolute_path, relative_to};
+use camino::Utf8Path;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// Prints a completion script for `shell`. Subcommands are completed from
+// `subcommands`, and everything else from the submodule paths printed by
+// `gm __complete`, so the script doesn't go stale as submodules change.
+pub fn print_script(shell: Shell, subcommands: &[&str]) {
+    let subcommands = subcommands.join(" ");
+    match shell {
+        Shell::Bash => println!(
+            r#"_gm() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{subcommands}" -- "$cur"))
+    elif [[ "$cur" != -* ]]; then
+        COMPREPLY=($(compgen -W "$(gm __complete 2>/dev/null)" -- "$cur"))
+    fi
+}}
+complete -o default -F _gm gm"#
+        ),
+        Shell::Zsh => println!(
+            r#"#compdef gm
+_gm() {{
+    if (( CURRENT == 2 )); then
+        compadd -- {subcommands}
+    elif [[ "$PREFIX" != -* ]]; then
+        compadd -- ${{(f)"$(gm __complete 2>/dev/null)"}}
+    fi
+}}
+compdef _gm gm"#
+        ),
+        Shell::Fish => println!(
+            r#"complete -c gm -f -n __fish_use_subcommand -a "{subcommands}"
+complete -c gm -f -n "not __fish_use_subcommand" -a "(gm __complete 2>/dev/null)""#
+        ),
+    }
+}
+
+// Lists the submodule paths relative to `cwd`. This only reads `.gitmodules`
+// so that it's fast enough to run on every tab press.
+pub fn submodule_paths(cwd: &Utf8Path) -> Result<Vec<String>, anyhow::Error> {
+    let repo = crate::discover(cwd)?;
+    let (Some(submodules), Some(work_dir)) = (repo.submodules()?, repo.work_dir()) else {
+        return Ok(Vec::new());
+    };
+    let work_dir = absolute_path(work_dir)?;
+    let cwd = absolute_path(cwd.as_std_path())?;
+
+    let mut paths = Vec::new();
+    for submodule in submodules {
+        let path = work_dir.join(gix::path::from_bstr(submodule.path()?));
+        paths.push(relative_to(&path, &cwd).to_string_lossy().into_owned());
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+// `gm __complete` must never print errors into the user's prompt, e.g. when
+// run outside of a repository.
+pub fn print_submodule_paths(cwd: &Utf8Path) {
+    if let Ok(paths) = submodule_paths(cwd) {
+        for path in paths {
+            println!("{}", path);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use colored::Colorize;
+use gix::bstr::ByteSlice;
+use gix::diff::blob::intern::InternedInput;
+use gix::diff::blob::{Algorithm, Sink};
+use gix::status::index_worktree::iter::Item;
+use gix::status::plumbing::index_as_worktree::{Change, EntryStatus};
+use std::ops::Range;
+
+// The number of unchanged lines shown around each change, like `git diff`.
+pub const DEFAULT_CONTEXT: u32 = 3;
+
+// Collects the changed line ranges so they can be grouped into hunks.
+struct Changes(Vec<(Range<u32>, Range<u32>)>);
+
+impl Sink for Changes {
+    type Out = Vec<(Range<u32>, Range<u32>)>;
+
+    fn process_change(&mut self, before: Range<u32>, after: Range<u32>) {
+        self.0.push((before, after));
+    }
+
+    fn finish(self) -> Self::Out {
+        self.0
+    }
+}
+
+fn print_line(prefix: char, line: &[u8], color: fn(&str) -> colored::ColoredString) {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end_matches(['\n', '\r']);
+    println!("      {}", color(&format!("{}{}", prefix, line)));
+}
+
+// Prints a unified diff between `old` and `new`, indented to sit under the
+// file name in `gm status`.
+pub fn print_diff(old: &[u8], new: &[u8], context: u32) {
+    if old.contains(&0) || new.contains(&0) {
+        println!("      {}", "binary file differs".dimmed());
+        return;
+    }
+
+    let input = InternedInput::new(old, new);
+    let changes = gix::diff::blob::diff(Algorithm::Histogram, &input, Changes(Vec::new()));
+    let before_len = input.before.len() as u32;
+    let line = |token| input.interner[token];
+
+    let mut idx = 0;
+    while idx < changes.len() {
+        // Changes closer together than twice the context share a hunk.
+        let mut end = idx + 1;
+        while end < changes.len() && changes[end].0.start - changes[end - 1].0.end <= 2 * context {
+            end += 1;
+        }
+        let hunk = &changes[idx..end];
+        let (first, last) = (&hunk[0], &hunk[hunk.len() - 1]);
+        let start = first.0.start.saturating_sub(context);
+        let after_start = first.1.start - (first.0.start - start);
+        let stop = (last.0.end + context).min(before_len);
+        let after_stop = last.1.end + (stop - last.0.end);
+
+        println!(
+            "      {}",
+            format!(
+                "@@ -{},{} +{},{} @@",
+                start + 1,
+                stop - start,
+                after_start + 1,
+                after_stop - after_start
+            )
+            .cyan()
+        );
+        let mut current = start;
+        for (before, after) in hunk {
+            for idx in current..before.start {
+                print_line(' ', line(input.before[idx as usize]), |s| s.dimmed());
+            }
+            for idx in before.clone() {
+                print_line('-', line(input.before[idx as usize]), |s| s.red());
+            }
+            for idx in after.clone() {
+                print_line('+', line(input.after[idx as usize]), |s| s.green());
+            }
+            current = before.end;
+        }
+        for idx in current..stop {
+            print_line(' ', line(input.before[idx as usize]), |s| s.dimmed());
+        }
+
+        idx = end;
+    }
+}
+
+// Prints the diff for a tracked file whose content changed in the worktree of
+// `repo`. Other kinds of changes have nothing to show.
+pub fn print_change_diff(
+    repo: &gix::Repository,
+    change: &Item,
+    context: u32,
+) -> Result<(), anyhow::Error> {
+    let Item::Modification {
+        entry,
+        rela_path,
+        status:
+            EntryStatus::Change(Change::Modification {
+                content_change: Some(_),
+                ..
+            }),
+        ..
+    } = change
+    else {
+        return Ok(());
+    };
+    let Some(work_dir) = repo.work_dir() else {
+        return Ok(());
+    };
+
+    let old = repo.find_object(entry.id)?.detach().data;
+    let new = std::fs::read(work_dir.join(gix::path::from_bstr(rela_path.as_bstr())))?;
+    print_diff(&old, &new, context);
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use crate::{discover, format_name};
+use camino::Utf8Path;
+use colored::Colorize;
+use gix::bstr::{BString, ByteSlice};
+use itertools::Itertools;
+
+fn problem(description: String, fix: String) {
+    println!("{} {}", "problem:".red().bold(), description);
+    println!("  {}", fix.dimmed());
+}
+
+pub fn doctor(cwd: &Utf8Path) -> Result<(), anyhow::Error> {
+    let repo = discover(cwd)?;
+    let Some(submodules) = repo.submodules()? else {
+        println!("No submodules found");
+        return Ok(());
+    };
+    let submodules = submodules
+        .sorted_by(|a, b| a.name().cmp(b.name()))
+        .collect::<Vec<_>>();
+    let mut problems = 0;
+
+    // `gm` shortens names to their last component, which can make two
+    // submodules look the same.
+    let by_display_name = submodules
+        .iter()
+        .map(|submodule| submodule.name().to_str_lossy().into_owned())
+        .into_group_map_by(|name| format_name(name).to_string());
+    for (display_name, names) in by_display_name.iter().sorted() {
+        if names.len() < 2 {
+            continue;
+        }
+        problems += 1;
+        problem(
+            match names.as_slice() {
+                [first, second] => format!(
+                    "{} and {} are both shown as {}",
+                    first,
+                    second,
+                    display_name.bold()
+                ),
+                _ => format!(
+                    "{} are all shown as {}",
+                    names.iter().join(", "),
+                    display_name.bold()
+                ),
+            },
+            "rename one of them in .gitmodules, or pick one with `--only <name>`".to_string(),
+        );
+    }
+
+    // Every path in `.gitmodules` needs a gitlink in the index, and every
+    // gitlink needs an entry in `.gitmodules`.
+    let index = repo.index_or_empty()?;
+    let gitlinks = index
+        .entries()
+        .iter()
+        .filter(|entry| entry.mode == gix::index::entry::Mode::COMMIT)
+        .map(|entry| BString::from(entry.path(&index)))
+        .collect::<Vec<_>>();
+    let mut declared = Vec::new();
+    for submodule in &submodules {
+        let path = submodule.path()?.into_owned();
+        if !gitlinks.contains(&path) {
+            problems += 1;
+            problem(
+                format!(
+                    "{} is at {} according to .gitmodules, but the index has no submodule there",
+                    submodule.name(),
+                    path
+                ),
+                format!(
+                    "set `submodule.{}.path` in .gitmodules to where the submodule is, or add it with `gm add`",
+                    submodule.name()
+                ),
+            );
+        }
+        declared.push(path);
+    }
+    for gitlink in gitlinks
+        .iter()
+        .filter(|gitlink| !declared.contains(gitlink))
+    {
+        problems += 1;
+        problem(
+            format!(
+                "the index has a submodule at {} that isn't in .gitmodules",
+                gitlink
+            ),
+            format!(
+                "point the right `path` in .gitmodules at it, or remove it with `git rm --cached {}`",
+                gitlink
+            ),
+        );
+    }
+
+    match problems {
+        0 => println!("{}", "no problems found".green().bold()),
+        1 => {
+            println!("{}", "found 1 problem".red().bold());
+            std::process::exit(1);
+        }
+        problems => {
+            println!("{}", format!("found {} problems", problems).red().bold());
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use crate::json;
+use crate::url::{self, UrlForm};
+use crate::{discover, display_name, select_submodules};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use gix::bstr::ByteSlice;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    /// JSON with the name, path, URL and commit of each submodule
+    Lockfile,
+    /// One `name url commit` line per submodule
+    Requirements,
+    /// Comma-separated `name,path,url,commit` rows after a header
+    Csv,
+}
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    /// Only export submodules at, under or containing these paths
+    paths: Vec<Utf8PathBuf>,
+    #[clap(long, value_enum, default_value = "lockfile")]
+    format: Format,
+}
+
+// Quotes a CSV field if it contains anything that would break the row apart,
+// doubling the quotes inside it.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+pub fn export(cwd: &Utf8Path, args: ExportArgs) -> Result<(), anyhow::Error> {
+    let repo = discover(cwd)?;
+    let modules = url::raw_modules(&repo)?;
+
+    let mut entries = Vec::new();
+    for submodule in select_submodules(&repo, cwd, &args.paths)? {
+        // Without a recorded commit there's nothing to pin.
+        let Some(commit) = submodule.index_id()? else {
+            eprintln!(
+                "{} {} {}",
+                "skipping".yellow().bold(),
+                display_name(&submodule)?,
+                "(not in index)".dimmed()
+            );
+            continue;
+        };
+        entries.push(json::ExportEntry {
+            name: submodule.name().to_str_lossy().into_owned(),
+            path: submodule.path()?.to_str_lossy().into_owned(),
+            url: url::submodule_url(&repo, modules.as_ref(), &submodule, UrlForm::Resolved)?,
+            commit: commit.to_string(),
+        });
+    }
+
+    match args.format {
+        // A lockfile outlives the version of `gm` that wrote it, so it
+        // always says which schema it follows.
+        Format::Lockfile => json::print(entries, true)?,
+        Format::Requirements => {
+            for entry in entries {
+                println!("{} {} {}", entry.name, entry.url, entry.commit);
+            }
+        }
+        Format::Csv => {
+            println!("name,path,url,commit");
+            for entry in entries {
+                println!(
+                    "{},{},{},{}",
+                    csv_field(&entry.name),
+                    csv_field(&entry.path),
+                    csv_field(&entry.url),
+                    csv_field(&entry.commit)
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::{absolute_path, discover, display_name, select_submodules, submodule_command};
+use camino::Utf8Path;
+use colored::Colorize;
+use gix::bstr::ByteSlice;
+use gix::submodule::config::Ignore;
+use itertools::Itertools;
+
+#[derive(clap::Args)]
+pub struct ForeachArgs {
+    /// Stop at the first submodule where the command fails
+    #[clap(long)]
+    fail_fast: bool,
+    /// Exit successfully even if the command failed in some submodules
+    #[clap(long)]
+    ignore_errors: bool,
+    /// Only run in submodules that are dirty before the command runs
+    #[clap(long)]
+    dirty_only: bool,
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+    command: Vec<String>,
+}
+
+pub fn foreach(cwd: &Utf8Path, args: ForeachArgs) -> Result<(), anyhow::Error> {
+    let repo = discover(cwd)?;
+    let Some(toplevel) = repo.work_dir() else {
+        anyhow::bail!("cannot run commands in submodules of a bare repository");
+    };
+    let toplevel = absolute_path(toplevel)?;
+    // Like `git submodule foreach`, the arguments form a single shell command.
+    let command = args.command.join(" ");
+
+    let mut submodules = Vec::new();
+    let mut skipped = Vec::new();
+    let mut uninitialized = Vec::new();
+    for submodule in select_submodules(&repo, cwd, &[])? {
+        if !submodule.state()?.worktree_checkout {
+            // Like `git submodule foreach`, these are passed over quietly,
+            // unless the run is already reporting what it leaves out.
+            if args.dirty_only {
+                uninitialized.push(display_name(&submodule)?);
+            }
+            continue;
+        }
+        // Decide up front, so that the command running in one submodule
+        // can't change which of the others it runs in.
+        if args.dirty_only && submodule.status(Ignore::None, true)?.is_dirty() != Some(true) {
+            skipped.push(display_name(&submodule)?);
+            continue;
+        }
+        submodules.push(submodule);
+    }
+    if !skipped.is_empty() {
+        println!(
+            "{} {}",
+            "skipping clean submodules:".dimmed(),
+            skipped.iter().join(", ")
+        );
+    }
+    if !uninitialized.is_empty() {
+        println!(
+            "{} {}",
+            "skipping uninitialized submodules:".dimmed(),
+            uninitialized.iter().join(", ")
+        );
+    }
+
+    let mut succeeded = 0;
+    let mut failures = Vec::new();
+    for submodule in submodules {
+        let name = display_name(&submodule)?;
+        let path = submodule.path()?.to_str_lossy().into_owned();
+        println!("{} {}", "entering".bold(), name);
+
+        let status = submodule_command("sh", &submodule.work_dir()?)
+            .arg("-c")
+            .arg(&command)
+            .env("name", submodule.name().to_str_lossy().as_ref())
+            .env("sm_path", &path)
+            .env("displaypath", &path)
+            .env(
+                "sha1",
+                submodule
+                    .index_id()?
+                    .map(|id| id.to_string())
+                    .unwrap_or_default(),
+            )
+            .env("toplevel", &toplevel)
+            .spawn()?
+            .wait()?;
+
+        if status.success() {
+            succeeded += 1;
+        } else {
+            failures.push((name, status.code()));
+            if args.fail_fast {
+                break;
+            }
+        }
+    }
+
+    print!(
+        "{} {}",
+        format!("{} succeeded,", succeeded).green().bold(),
+        format!("{} failed", failures.len()).red().bold(),
+    );
+    if args.fail_fast && !failures.is_empty() {
+        print!(" {}", "(stopped at first failure)".dimmed());
+    }
+    println!();
+    for (name, code) in &failures {
+        match code {
+            Some(code) => println!(
+                "  {} {}",
+                name.clone().red(),
+                format!("exited with {}", code).dimmed()
+            ),
+            None => println!(
+                "  {} {}",
+                name.clone().red(),
+                "was terminated by a signal".dimmed()
+            ),
+        }
+    }
+
+    if !failures.is_empty() && !args.ignore_errors {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
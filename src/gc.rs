@@ -0,0 +1,103 @@
+use crate::{
+    discover, display_name, format_bytes, git_binary, parallel_map, print_skipped,
+    select_submodules, submodule_command,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use gix::bstr::ByteSlice;
+use std::path::Path;
+
+#[derive(clap::Args)]
+pub struct GcArgs {
+    paths: Vec<Utf8PathBuf>,
+    /// Pass `--aggressive` to `git gc`
+    #[clap(long)]
+    aggressive: bool,
+    /// How many submodules to collect at once; defaults to the number of CPUs
+    #[clap(long, short)]
+    jobs: Option<usize>,
+}
+
+// Adds up the size of all files under `path`.
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+pub fn gc(cwd: &Utf8Path, args: GcArgs) -> Result<(), anyhow::Error> {
+    let git = git_binary("`gm gc`")?;
+    let repo = discover(cwd)?;
+    let mut targets = Vec::new();
+    for submodule in select_submodules(&repo, cwd, &args.paths)? {
+        let name = display_name(&submodule)?;
+        if !submodule.state()?.worktree_checkout {
+            print_skipped(&name, "uninitialized");
+            continue;
+        }
+        targets.push((
+            name,
+            submodule.work_dir()?,
+            submodule.git_dir_try_old_form()?,
+        ));
+    }
+
+    let results = parallel_map(&targets, args.jobs, |(_, work_dir, git_dir)| {
+        let before = dir_size(git_dir).ok();
+        let mut command = submodule_command(&git, work_dir);
+        command.arg("gc").arg("--quiet");
+        if args.aggressive {
+            command.arg("--aggressive");
+        }
+        let output = command.output();
+        let after = dir_size(git_dir).ok();
+        (output, before.zip(after))
+    });
+
+    let mut total = 0;
+    let mut failed = false;
+    for ((name, _, _), (output, sizes)) in targets.iter().zip(results) {
+        let output = output?;
+        if !output.status.success() {
+            failed = true;
+            println!("{} {}", "failed to gc".red().bold(), name);
+            for line in output.stderr.lines() {
+                println!("  {}", line.to_str_lossy().dimmed());
+            }
+            continue;
+        }
+        match sizes {
+            Some((before, after)) => {
+                let reclaimed = before.saturating_sub(after);
+                total += reclaimed;
+                println!(
+                    "{} {} {} {}",
+                    "collected".bold(),
+                    name,
+                    "reclaiming".bold(),
+                    format_bytes(reclaimed).green()
+                );
+            }
+            None => println!("{} {}", "collected".bold(), name),
+        }
+    }
+    println!(
+        "{} {}",
+        "reclaimed".bold(),
+        format_bytes(total).green().bold()
+    );
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
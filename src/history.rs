@@ -0,0 +1,198 @@
+use gix::bstr::{BStr, BString, ByteSlice};
+use gix::ObjectId;
+use std::collections::{HashMap, HashSet};
+
+// How many superproject commits we're willing to walk before giving up on
+// finding the commit that last moved a submodule pointer.
+pub const MAX_BLAME_COMMITS: usize = 1000;
+
+// The superproject commit that last changed a submodule's gitlink.
+#[derive(Clone)]
+pub struct PointerChange {
+    pub commit: ObjectId,
+    pub author: String,
+    pub time: gix::date::Time,
+}
+
+// Looks up the gitlink recorded at `path` in `tree`, if any. `tree` is left
+// as it is, so it can be used to look up more paths.
+fn gitlink_at(tree: &gix::Tree, path: &BStr) -> Result<Option<ObjectId>, anyhow::Error> {
+    Ok(tree
+        .lookup_entry_by_path(gix::path::from_bstr(path), &mut Vec::new())?
+        .filter(|entry| entry.mode().is_commit())
+        .map(|entry| entry.object_id()))
+}
+
+// Walks the first-parent history of `HEAD` once and finds, for each of the
+// given paths, the most recent commit that changed the gitlink at that path.
+// Paths whose change is further back than `limit` commits are left out.
+pub fn blame_pointers(
+    repo: &gix::Repository,
+    paths: &[BString],
+    limit: usize,
+) -> Result<HashMap<BString, PointerChange>, anyhow::Error> {
+    let mut blamed = HashMap::new();
+    let Ok(head) = repo.head_id() else {
+        return Ok(blamed);
+    };
+
+    let mut remaining: Vec<&BString> = paths.iter().collect();
+    // The commit we looked at previously (i.e. the child of the current one)
+    // and the gitlinks it recorded.
+    let mut newer: Option<(PointerChange, HashMap<&BString, Option<ObjectId>>)> = None;
+    let mut exhausted = true;
+
+    for (idx, info) in repo.rev_walk([head]).first_parent_only().all()?.enumerate() {
+        if remaining.is_empty() {
+            break;
+        }
+        if idx >= limit {
+            exhausted = false;
+            break;
+        }
+
+        let commit = info?.object()?;
+        let tree = commit.tree()?;
+        let mut gitlinks = HashMap::new();
+        for path in &remaining {
+            gitlinks.insert(*path, gitlink_at(&tree, path.as_bstr())?);
+        }
+
+        if let Some((change, newer_gitlinks)) = newer.take() {
+            let (moved, unmoved): (Vec<_>, Vec<_>) = remaining
+                .into_iter()
+                .partition(|path| newer_gitlinks[path] != gitlinks[path]);
+            for path in moved {
+                blamed.insert(path.clone(), change.clone());
+            }
+            remaining = unmoved;
+        }
+
+        let author = commit.author()?;
+        newer = Some((
+            PointerChange {
+                commit: commit.id,
+                author: author.name.to_str_lossy().into_owned(),
+                time: author.time,
+            },
+            gitlinks,
+        ));
+    }
+
+    // If we reached the root commit, it's the one that introduced whatever
+    // gitlinks are still unaccounted for.
+    if exhausted {
+        if let Some((change, gitlinks)) = newer {
+            for path in remaining {
+                if gitlinks[path].is_some() {
+                    blamed.insert(path.clone(), change.clone());
+                }
+            }
+        }
+    }
+
+    Ok(blamed)
+}
+
+// A superproject commit that changed a gitlink, and what it changed it to.
+// `gitlink` is `None` if the commit removed the submodule.
+pub struct Transition {
+    pub change: PointerChange,
+    pub gitlink: Option<ObjectId>,
+}
+
+// Lists the commits on the first-parent history of `HEAD` that changed the
+// gitlink at `path`, oldest first. With a `limit`, only that many of the most
+// recent ones are returned.
+pub fn pointer_history(
+    repo: &gix::Repository,
+    path: &BStr,
+    limit: Option<usize>,
+) -> Result<Vec<Transition>, anyhow::Error> {
+    let mut transitions = Vec::new();
+    let Ok(head) = repo.head_id() else {
+        return Ok(transitions);
+    };
+
+    let mut newer: Option<Transition> = None;
+    let mut exhausted = true;
+    for info in repo.rev_walk([head]).first_parent_only().all()? {
+        if limit.is_some_and(|limit| transitions.len() >= limit) {
+            exhausted = false;
+            break;
+        }
+
+        let commit = info?.object()?;
+        let gitlink = gitlink_at(&commit.tree()?, path)?;
+        if let Some(transition) = newer.take() {
+            if transition.gitlink != gitlink {
+                transitions.push(transition);
+            }
+        }
+
+        let author = commit.author()?;
+        newer = Some(Transition {
+            change: PointerChange {
+                commit: commit.id,
+                author: author.name.to_str_lossy().into_owned(),
+                time: author.time,
+            },
+            gitlink,
+        });
+    }
+
+    // The root commit introduced the gitlink if it has one.
+    if exhausted {
+        if let Some(transition) = newer.filter(|transition| transition.gitlink.is_some()) {
+            transitions.push(transition);
+        }
+    }
+    if let Some(limit) = limit {
+        transitions.truncate(limit);
+    }
+    transitions.reverse();
+
+    Ok(transitions)
+}
+
+// How many commits we're willing to walk on each side when counting how far
+// apart two commits are.
+pub const MAX_AHEAD_BEHIND_COMMITS: usize = 10_000;
+
+// Collects the commits reachable from `tip`, or `None` if there are more than
+// `limit` of them.
+fn ancestors(
+    repo: &gix::Repository,
+    tip: ObjectId,
+    limit: usize,
+) -> Result<Option<HashSet<ObjectId>>, anyhow::Error> {
+    let mut ancestors = HashSet::new();
+    for info in repo.rev_walk([tip]).all()? {
+        if ancestors.len() >= limit {
+            return Ok(None);
+        }
+        ancestors.insert(info?.id);
+    }
+    Ok(Some(ancestors))
+}
+
+// Counts the commits reachable from `head` but not from `base` and the other
+// way around, or returns `None` if either history is longer than `limit`.
+pub fn ahead_behind(
+    repo: &gix::Repository,
+    head: ObjectId,
+    base: ObjectId,
+    limit: usize,
+) -> Result<Option<(usize, usize)>, anyhow::Error> {
+    if head == base {
+        return Ok(Some((0, 0)));
+    }
+    let (Some(head), Some(base)) = (ancestors(repo, head, limit)?, ancestors(repo, base, limit)?)
+    else {
+        return Ok(None);
+    };
+    Ok(Some((
+        head.difference(&base).count(),
+        base.difference(&head).count(),
+    )))
+}
@@ -0,0 +1,154 @@
+use crate::json::{self, Commit};
+use crate::url::{self, UrlForm};
+use crate::{discover, display_name, history, select_submodules};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use gix::bstr::ByteSlice;
+use gix::submodule::config::{Branch, Ignore};
+
+#[derive(clap::Args)]
+pub struct InfoArgs {
+    /// Only show submodules at, under or containing these paths
+    paths: Vec<Utf8PathBuf>,
+    /// Print the details as JSON
+    #[clap(long)]
+    json: bool,
+    /// Wrap the JSON output in an object with the schema version
+    #[clap(long, requires = "json")]
+    schema_version: bool,
+}
+
+// Collects everything `gm` knows about a submodule.
+fn entry(
+    repo: &gix::Repository,
+    modules: Option<&gix::submodule::File>,
+    submodule: &gix::Submodule,
+) -> Result<json::InfoEntry, anyhow::Error> {
+    let recorded = submodule.index_id()?;
+    let sm_repo = submodule.open()?;
+    let head = sm_repo
+        .as_ref()
+        .and_then(|sm_repo| sm_repo.head_id().ok())
+        .map(|id| id.detach());
+    let checked_out_branch = match &sm_repo {
+        Some(sm_repo) => sm_repo
+            .head_name()?
+            .map(|name| name.shorten().to_str_lossy().into_owned()),
+        None => None,
+    };
+    // How far the checked out commit is from the recorded one.
+    let ahead_behind = match (&sm_repo, head, recorded) {
+        (Some(sm_repo), Some(head), Some(recorded)) if sm_repo.has_object(recorded) => {
+            history::ahead_behind(sm_repo, head, recorded, history::MAX_AHEAD_BEHIND_COMMITS)?
+        }
+        _ => None,
+    };
+    let status = submodule.status(Ignore::None, false)?;
+
+    Ok(json::InfoEntry {
+        name: submodule.name().to_str_lossy().into_owned(),
+        path: submodule.path()?.to_str_lossy().into_owned(),
+        url: json::Urls {
+            raw: url::submodule_url(repo, modules, submodule, UrlForm::Raw)?,
+            resolved: url::submodule_url(repo, modules, submodule, UrlForm::Resolved)?,
+        },
+        branch: submodule.branch()?.map(|branch| match branch {
+            Branch::Name(name) => name.to_str_lossy().into_owned(),
+            Branch::CurrentInSuperproject => ".".to_string(),
+        }),
+        recorded_commit: recorded.map(Commit::new),
+        checked_out_commit: head.map(Commit::new),
+        detached: head.map(|_| checked_out_branch.is_none()),
+        checked_out_branch,
+        ahead: ahead_behind.map(|(ahead, _)| ahead),
+        behind: ahead_behind.map(|(_, behind)| behind),
+        dirty: status.is_dirty(),
+        changes: status
+            .changes
+            .unwrap_or_default()
+            .iter()
+            .filter_map(json::Change::from_item)
+            .collect(),
+    })
+}
+
+pub fn info(cwd: &Utf8Path, args: InfoArgs) -> Result<(), anyhow::Error> {
+    let repo = discover(cwd)?;
+    let modules = url::raw_modules(&repo)?;
+    let submodules = select_submodules(&repo, cwd, &args.paths)?;
+
+    if args.json {
+        let entries = submodules
+            .iter()
+            .map(|submodule| entry(&repo, modules.as_ref(), submodule))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        return json::print(entries, args.schema_version);
+    }
+
+    for submodule in &submodules {
+        let info = entry(&repo, modules.as_ref(), submodule)?;
+        println!("{} {}", display_name(submodule)?, info.path.dimmed());
+        if info.url.raw == info.url.resolved {
+            println!("  {} {}", "url".bold(), info.url.raw);
+        } else {
+            println!(
+                "  {} {} {}",
+                "url".bold(),
+                info.url.raw,
+                format!("({})", info.url.resolved).dimmed()
+            );
+        }
+        if let Some(branch) = &info.branch {
+            println!("  {} {}", "tracks".bold(), branch);
+        }
+        let Some(recorded) = &info.recorded_commit else {
+            println!("  {} {}", "recorded".bold(), "not in index".dimmed());
+            continue;
+        };
+        println!("  {} {}", "recorded".bold(), recorded.short);
+        let Some(checked_out) = &info.checked_out_commit else {
+            println!("  {} {}", "checked out".bold(), "nothing".dimmed());
+            continue;
+        };
+        match &info.checked_out_branch {
+            Some(branch) => println!(
+                "  {} {} {}",
+                "checked out".bold(),
+                checked_out.short,
+                format!("on {}", branch).green()
+            ),
+            None => println!(
+                "  {} {} {}",
+                "checked out".bold(),
+                checked_out.short,
+                "(detached)".dimmed()
+            ),
+        }
+        if let (Some(ahead), Some(behind)) = (info.ahead, info.behind) {
+            if ahead > 0 || behind > 0 {
+                println!(
+                    "  {} {} ahead, {} behind the recorded commit",
+                    "position".bold(),
+                    ahead,
+                    behind
+                );
+            }
+        }
+        match info.dirty {
+            Some(true) if info.changes.len() == 1 => println!(
+                "  {} {}",
+                "dirty".yellow().bold(),
+                "(1 changed file)".dimmed()
+            ),
+            Some(true) => println!(
+                "  {} {}",
+                "dirty".yellow().bold(),
+                format!("({} changed files)", info.changes.len()).dimmed()
+            ),
+            Some(false) => println!("  {}", "clean".green().bold()),
+            None => {}
+        }
+    }
+
+    Ok(())
+}
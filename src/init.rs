@@ -0,0 +1,315 @@
+use crate::url::{self, UrlForm};
+use crate::{
+    display_name, git_binary, parallel_map, relative_to, select_submodules, set_config,
+    submodule_command, NO_SHELL_OUT,
+};
+use camino::Utf8Path;
+use colored::{ColoredString, Colorize};
+use gix::bstr::ByteSlice;
+use gix::ObjectId;
+use itertools::Itertools;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Everything a worker thread needs to clone a submodule into place.
+// `gix::Submodule` borrows the superproject, so it can't be sent across.
+struct Target {
+    name: ColoredString,
+    url: String,
+    git_dir: PathBuf,
+    work_dir: PathBuf,
+    recorded: ObjectId,
+}
+
+// gix speaks these transports itself. Everything else, notably http(s), is
+// left to the git binary. Local clones still run `git upload-pack`, so they
+// don't count with `--no-shell-out`.
+fn is_native_transport(url: &str) -> bool {
+    use gix::url::Scheme;
+    match gix::Url::try_from(url).map(|url| url.scheme) {
+        Ok(Scheme::Ssh | Scheme::Git) => true,
+        Ok(Scheme::File) => !NO_SHELL_OUT.load(Ordering::Relaxed),
+        _ => false,
+    }
+}
+
+// Clones the submodule into its git directory under `.git/modules`, turns
+// that into the submodule's repository and checks out the recorded commit,
+// which is what `git submodule update` does for a new submodule.
+fn clone_into_place(target: &Target) -> Result<(), anyhow::Error> {
+    if std::fs::read_dir(&target.work_dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        anyhow::bail!(
+            "{} already exists and isn't empty",
+            target.work_dir.display()
+        );
+    }
+    let should_interrupt = AtomicBool::new(false);
+    if let Some(parent) = target.git_dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let (repo, _) = gix::prepare_clone_bare(target.url.as_str(), &target.git_dir)?
+        .fetch_only(gix::progress::Discard, &should_interrupt)?;
+    if !repo.has_object(target.recorded) {
+        anyhow::bail!(
+            "the recorded commit {} isn't on any branch of {}",
+            target.recorded.to_hex_with_len(7),
+            target.url
+        );
+    }
+    drop(repo);
+
+    std::fs::create_dir_all(&target.work_dir)?;
+    let work_dir = crate::absolute_path(&target.work_dir)?;
+    let git_dir = crate::absolute_path(&target.git_dir)?;
+    set_config(
+        &git_dir.join("config"),
+        &[
+            ("core", None, "bare", "false"),
+            (
+                "core",
+                None,
+                "worktree",
+                &relative_to(&work_dir, &git_dir).to_string_lossy(),
+            ),
+        ],
+    )?;
+    std::fs::write(
+        work_dir.join(".git"),
+        format!("gitdir: {}\n", relative_to(&git_dir, &work_dir).display()),
+    )?;
+    std::fs::write(git_dir.join("HEAD"), format!("{}\n", target.recorded))?;
+
+    let repo = gix::open(&work_dir)?;
+    let tree = repo.find_commit(target.recorded)?.tree_id()?;
+    let mut index = repo.index_from_tree(&tree)?;
+    let options = gix::worktree::state::checkout::Options {
+        destination_is_initially_empty: true,
+        // The submodules are already checked out in parallel.
+        thread_limit: Some(1),
+        ..Default::default()
+    };
+    gix::worktree::state::checkout(
+        &mut index,
+        &work_dir,
+        repo.objects.clone().into_arc()?,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &should_interrupt,
+        options,
+    )?;
+    index.write(Default::default())?;
+
+    Ok(())
+}
+
+// Initializes and checks out every submodule of `repo` that isn't yet,
+// adding the ones that fail to `failed`. Submodules with a URL gix can fetch
+// from are cloned natively, up to `jobs` at a time; the rest, and those with a
+// git directory left over from before, go through `git submodule update`.
+// `nested` is set for the repositories of submodules when recursing.
+fn init_submodules(
+    repo: &gix::Repository,
+    cwd: &Utf8Path,
+    jobs: Option<usize>,
+    recursive: bool,
+    nested: bool,
+    failed: &mut Vec<ColoredString>,
+) -> Result<(), anyhow::Error> {
+    let Some(toplevel) = repo.work_dir() else {
+        anyhow::bail!("cannot initialize submodules of a bare repository");
+    };
+    let modules = url::raw_modules(repo)?;
+
+    let mut targets = Vec::new();
+    let mut fallback = Vec::new();
+    let mut config = Vec::new();
+    for submodule in select_submodules(repo, cwd, &[])? {
+        let state = submodule.state()?;
+        if state.repository_exists && state.worktree_checkout {
+            continue;
+        }
+        let url = url::submodule_url(repo, modules.as_ref(), &submodule, UrlForm::Resolved)?;
+        match submodule.index_id()? {
+            Some(recorded) if !state.repository_exists && is_native_transport(&url) => {
+                config.push((submodule.name().to_owned(), url.clone()));
+                targets.push(Target {
+                    name: display_name(&submodule)?,
+                    url,
+                    git_dir: submodule.git_dir(),
+                    work_dir: submodule.work_dir()?,
+                    recorded,
+                });
+            }
+            _ => fallback.push(submodule),
+        }
+    }
+
+    // Register the submodules in `.git/config` like `git submodule init`.
+    if !config.is_empty() {
+        let values = config
+            .iter()
+            .flat_map(|(name, url)| {
+                [
+                    ("submodule", Some(name.as_bstr()), "url", url.as_str()),
+                    ("submodule", Some(name.as_bstr()), "active", "true"),
+                ]
+            })
+            .collect::<Vec<_>>();
+        set_config(&repo.common_dir().join("config"), &values)?;
+    }
+
+    let results = parallel_map(&targets, jobs, clone_into_place);
+    for (target, result) in targets.iter().zip(results) {
+        match result {
+            Ok(()) => println!(
+                "{} {} {} {}",
+                "initialized".bold(),
+                target.name,
+                "at".bold(),
+                target.recorded.to_hex_with_len(7).to_string().dimmed()
+            ),
+            Err(err) => {
+                println!("{} {}", "failed to initialize".red().bold(), target.name);
+                println!("  {}", err.to_string().dimmed());
+                failed.push(target.name.clone());
+            }
+        }
+    }
+
+    if !fallback.is_empty() {
+        let git = git_binary(&format!(
+            "initializing {}",
+            fallback
+                .iter()
+                .map(display_name)
+                .collect::<Result<Vec<_>, _>>()?
+                .iter()
+                .join(", ")
+        ))?;
+        // Only the outermost repository may pick up `--git-dir`.
+        let git_command = || match nested {
+            true => submodule_command(&git, toplevel),
+            false => {
+                let mut command = Command::new(&git);
+                command.current_dir(toplevel);
+                command
+            }
+        };
+        let paths = fallback
+            .iter()
+            .map(|submodule| Ok(gix::path::from_bstr(submodule.path()?).into_owned()))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        git_command()
+            .arg("submodule")
+            .arg("init")
+            .arg("--")
+            .args(&paths)
+            .status()?;
+
+        let mut command = git_command();
+        command.arg("submodule").arg("update");
+        if recursive {
+            command.arg("--recursive");
+        }
+        if let Some(jobs) = jobs {
+            command.arg("--jobs").arg(jobs.to_string());
+        }
+        command.arg("--").args(&paths).status()?;
+
+        for submodule in &fallback {
+            let state = submodule.state()?;
+            if state.repository_exists && state.worktree_checkout {
+                println!(
+                    "{} {} {}",
+                    "initialized".bold(),
+                    display_name(submodule)?,
+                    "(with git)".dimmed()
+                );
+            } else {
+                println!(
+                    "{} {}",
+                    "failed to initialize".red().bold(),
+                    display_name(submodule)?
+                );
+                failed.push(display_name(submodule)?);
+            }
+        }
+    }
+
+    if recursive {
+        for submodule in select_submodules(repo, cwd, &[])? {
+            if !submodule.state()?.worktree_checkout {
+                continue;
+            }
+            if let Some(sm_repo) = submodule.open()? {
+                if sm_repo.submodules()?.is_some() {
+                    init_submodules(&sm_repo, cwd, jobs, recursive, true, failed)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Prints the initialized submodules of `repo` and, indented under each, the
+// ones nested in it. `visited` holds the worktrees printed so far, so that
+// symlinks can't send this around in circles.
+fn print_tree(
+    repo: &gix::Repository,
+    prefix: &Path,
+    depth: usize,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    for submodule in repo
+        .submodules()?
+        .into_iter()
+        .flatten()
+        .sorted_by(|a, b| a.name().cmp(b.name()))
+    {
+        let state = submodule.state()?;
+        if !state.repository_exists || !state.worktree_checkout {
+            continue;
+        }
+        let path = prefix.join(gix::path::from_bstr(submodule.path()?));
+        print!(
+            "{}{} {}",
+            "  ".repeat(depth),
+            display_name(&submodule)?,
+            path.display().to_string().dimmed()
+        );
+        if !visited.insert(crate::absolute_path(&submodule.work_dir()?)?) {
+            println!(" {}", "(already shown)".dimmed());
+            continue;
+        }
+        println!();
+        if let Some(sm_repo) = submodule.open()? {
+            print_tree(&sm_repo, &path, depth + 1, visited)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn init(cwd: &Utf8Path, jobs: Option<usize>, recursive: bool) -> Result<(), anyhow::Error> {
+    let repo = crate::discover(cwd)?;
+    let mut failed = Vec::new();
+    init_submodules(&repo, cwd, jobs, recursive, false, &mut failed)?;
+
+    if recursive {
+        println!("{}", "initialized submodules:".bold());
+        print_tree(&repo, Path::new(""), 1, &mut HashSet::new())?;
+    }
+
+    if !failed.is_empty() {
+        println!("{}", "failed to initialize:".red().bold());
+        for name in failed {
+            println!("  {}", name);
+        }
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use camino::Utf8Path;
+use gix::bstr::ByteSlice;
+use gix::status::index_worktree::iter::{Item, RewriteSource, Summary};
+use serde::{Deserialize, Serialize};
+
+// Bump this whenever the shape of the JSON output changes, and describe the
+// change in the README.
+pub const SCHEMA_VERSION: u32 = 7;
+
+#[derive(Serialize)]
+struct Versioned<T> {
+    version: u32,
+    submodules: T,
+}
+
+// Prints `submodules` as JSON, wrapped in an object carrying the schema
+// version if one was requested.
+pub fn print<T: Serialize>(submodules: T, schema_version: bool) -> Result<(), anyhow::Error> {
+    let output = if schema_version {
+        serde_json::to_string_pretty(&Versioned {
+            version: SCHEMA_VERSION,
+            submodules,
+        })?
+    } else {
+        serde_json::to_string_pretty(&submodules)?
+    };
+    println!("{}", output);
+
+    Ok(())
+}
+
+// The parts of a `StatusEntry` that `gm status --diff-baseline` compares.
+#[derive(Deserialize)]
+pub struct BaselineEntry {
+    pub name: String,
+    pub state: State,
+    pub recorded_commit: Option<String>,
+    pub checked_out_commit: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Baseline {
+    version: u32,
+    submodules: Vec<BaselineEntry>,
+}
+
+// Saves the output of `gm status --json`, with the schema version, as a
+// baseline for later runs.
+pub fn write_baseline(path: &Utf8Path, entries: &[StatusEntry]) -> Result<(), anyhow::Error> {
+    let baseline = serde_json::to_string_pretty(&Versioned {
+        version: SCHEMA_VERSION,
+        submodules: entries,
+    })?;
+    std::fs::write(path, baseline + "\n")?;
+    Ok(())
+}
+
+pub fn read_baseline(path: &Utf8Path) -> Result<Vec<BaselineEntry>, anyhow::Error> {
+    let baseline: Baseline = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    // A newer schema may have changed the fields compared here.
+    if baseline.version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "{} was recorded by a newer version of gm (schema version {})",
+            path,
+            baseline.version
+        );
+    }
+    Ok(baseline.submodules)
+}
+
+#[derive(Serialize)]
+pub struct LsEntry {
+    pub name: String,
+    pub path: String,
+    pub url: String,
+    pub initialized: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    Clean,
+    Dirty,
+    Unknown,
+    Uninitialized,
+    MissingWorktree,
+    AssumedClean,
+}
+
+#[derive(Serialize)]
+pub struct LastMoved {
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+}
+
+#[derive(Serialize)]
+pub struct Transition {
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+    pub gitlink: Option<String>,
+}
+
+impl Transition {
+    pub fn new(transition: &crate::history::Transition) -> Self {
+        Transition {
+            commit: transition.change.commit.to_string(),
+            author: transition.change.author.clone(),
+            date: transition
+                .change
+                .time
+                .format(gix::date::time::format::SHORT),
+            gitlink: transition.gitlink.map(|id| id.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StatusEntry {
+    pub name: String,
+    pub path: String,
+    pub url: String,
+    pub state: State,
+    pub operation: Option<&'static str>,
+    pub recorded_commit: Option<String>,
+    pub checked_out_commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_moved: Option<LastMoved>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Serialize)]
+pub struct Commit {
+    pub full: String,
+    pub short: String,
+}
+
+impl Commit {
+    pub fn new(id: gix::ObjectId) -> Self {
+        Commit {
+            full: id.to_string(),
+            short: id.to_hex_with_len(7).to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Urls {
+    pub raw: String,
+    pub resolved: String,
+}
+
+#[derive(Serialize)]
+pub struct InfoEntry {
+    pub name: String,
+    pub path: String,
+    pub url: Urls,
+    pub branch: Option<String>,
+    pub recorded_commit: Option<Commit>,
+    pub checked_out_commit: Option<Commit>,
+    pub checked_out_branch: Option<String>,
+    pub detached: Option<bool>,
+    pub ahead: Option<usize>,
+    pub behind: Option<usize>,
+    pub dirty: Option<bool>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Serialize)]
+pub struct ExportEntry {
+    pub name: String,
+    pub path: String,
+    pub url: String,
+    pub commit: String,
+}
+
+#[derive(Serialize)]
+pub struct Change {
+    pub path: String,
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Change {
+    // Returns `None` for items that don't show up in `gm status`, like
+    // entries that merely need their stat information refreshed.
+    pub fn from_item(item: &Item) -> Option<Self> {
+        let kind = match item.summary()? {
+            Summary::Modified if crate::is_mode_only(item) => "mode-change",
+            Summary::Removed => "removed",
+            Summary::Added => "untracked",
+            Summary::Modified => "modified",
+            Summary::TypeChange => "type-change",
+            Summary::Renamed => "renamed",
+            Summary::Copied => "copied",
+            Summary::IntentToAdd => "intent-to-add",
+            Summary::Conflict => "conflict",
+        };
+        let source = match item {
+            Item::Rewrite {
+                source:
+                    RewriteSource::RewriteFromIndex {
+                        source_rela_path, ..
+                    },
+                ..
+            } => Some(source_rela_path.to_str_lossy().into_owned()),
+            Item::Rewrite {
+                source:
+                    RewriteSource::CopyFromDirectoryEntry {
+                        source_dirwalk_entry,
+                        ..
+                    },
+                ..
+            } => Some(source_dirwalk_entry.rela_path.to_str_lossy().into_owned()),
+            _ => None,
+        };
+
+        Some(Change {
+            path: item.rela_path().to_str_lossy().into_owned(),
+            kind,
+            source,
+        })
+    }
+}
@@ -1,21 +1,76 @@
 #![feature(slice_split_once)]
 
-use camino::Utf8PathBuf;
-use clap::Parser;
+use add::AddArgs;
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{CommandFactory, Parser};
 use colored::{ColoredString, Colorize};
-use gix::bstr::ByteSlice;
+use export::ExportArgs;
+use foreach::ForeachArgs;
+use gc::GcArgs;
+use gix::bstr::{BStr, ByteSlice};
 use gix::status::index_worktree::iter::{Item, RewriteSource};
-use gix::status::plumbing::index_as_worktree::EntryStatus;
-use gix::submodule::config::Ignore;
+use gix::status::plumbing::index_as_worktree::{Change, EntryStatus};
 use gix::Url;
+use info::InfoArgs;
 use itertools::Itertools;
+use reset::ResetArgs;
+use rm::RmArgs;
+use set_pushurl::SetPushurlArgs;
+use status::StatusArgs;
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use tracing::debug;
+use update::UpdateArgs;
+use url::UrlForm;
+
+mod add;
+mod completions;
+mod diff;
+mod doctor;
+mod export;
+mod foreach;
+mod gc;
+mod history;
+mod info;
+mod init;
+mod json;
+mod reset;
+mod rm;
+mod set_pushurl;
+mod status;
+mod update;
+mod url;
+
+// Exit code for `gm status --fail-on-uninitialized` when at least one
+// submodule isn't initialized.
+const EXIT_UNINITIALIZED: i32 = 3;
+
+// Exit code for `gm status --only-conflicts-exit-code` when at least one
+// submodule has conflicts, and for `gm update --merge` or `--rebase` when
+// they ran into some.
+const EXIT_CONFLICTS: i32 = 4;
 
 #[derive(Parser)]
 struct Args {
     #[clap(long)]
     cwd: Option<Utf8PathBuf>,
+    /// Use this git directory instead of discovering the repository, like
+    /// `git --git-dir`
+    #[clap(long, value_name = "PATH")]
+    git_dir: Option<Utf8PathBuf>,
+    /// The worktree of the repository given with `--git-dir`
+    #[clap(long, value_name = "PATH", requires = "git_dir")]
+    work_tree: Option<Utf8PathBuf>,
+    /// Run the command in every git repository in this directory and the
+    /// directories under it, but not in repositories nested in those
+    #[clap(long, value_name = "DIR", conflicts_with_all = ["cwd", "git_dir"])]
+    repos: Option<Utf8PathBuf>,
+    /// Never run the git binary, failing instead for anything `gm` can't yet
+    /// do by itself
+    #[clap(long)]
+    no_shell_out: bool,
     #[clap(subcommand)]
     command: Subcommand,
 }
@@ -26,17 +81,375 @@ enum Subcommand {
     Clone {
         url: String,
         path: Option<Utf8PathBuf>,
+        /// Don't check out a working tree. The submodules are listed from the
+        /// fetched `.gitmodules`; run `git checkout` and then `gm init` to
+        /// check them out
+        #[clap(long, short = 'n')]
+        no_checkout: bool,
+        /// Put the superproject's git directory at this path, leaving a
+        /// `.git` file pointing to it in the worktree
+        #[clap(long, value_name = "PATH")]
+        separate_git_dir: Option<Utf8PathBuf>,
+        /// Prefix each line git prints with the name of the repository it's
+        /// about, to make logs of recursive clones easier to follow
+        #[clap(long)]
+        prefix_output: bool,
+        /// Clone the submodules too. This is the default unless `GM_RECURSE`
+        /// or `submodule.recurse` is set to `false`
+        #[clap(long, alias = "recurse-submodules", overrides_with = "no_recursive")]
+        recursive: bool,
+        /// Only clone the superproject, listing its submodules
+        #[clap(long, alias = "no-recurse-submodules")]
+        no_recursive: bool,
     },
+    /// Add a submodule
+    Add(AddArgs),
     /// Remove a submodule
-    Rm { path: Utf8PathBuf },
+    Rm(RmArgs),
     /// Initialize submodules
-    Init,
+    Init {
+        /// How many submodules to clone at once; defaults to the number of CPUs
+        #[clap(long, short)]
+        jobs: Option<usize>,
+        /// Also initialize the submodules nested in submodules
+        #[clap(long, short)]
+        recursive: bool,
+    },
     /// Pull and update submodules
     Pull { args: Vec<String> },
     /// List submodules
-    Ls,
+    Ls {
+        /// Print the submodules as JSON
+        #[clap(long)]
+        json: bool,
+        /// Wrap the JSON output in an object with the schema version
+        #[clap(long, requires = "json")]
+        schema_version: bool,
+        /// Show each submodule's URL, with relative URLs either as written
+        /// (`raw`) or resolved against the superproject's remote (`resolved`)
+        #[clap(long, value_enum)]
+        emit_relative_urls: Option<UrlForm>,
+    },
     /// Show submodules and their changed files
-    Status,
+    Status(StatusArgs),
+    /// Show the details of each submodule
+    Info(InfoArgs),
+    /// Check for problems with the submodule setup, like names that look the
+    /// same or paths in `.gitmodules` that don't match the index
+    Doctor,
+    /// Print the URL and recorded commit of each submodule for tools that
+    /// don't speak git
+    Export(ExportArgs),
+    /// Check out each submodule at the commit recorded in the superproject
+    Reset(ResetArgs),
+    /// Initialize and check out submodules at their recorded commits
+    Update(UpdateArgs),
+    /// Run `git gc` in each initialized submodule
+    Gc(GcArgs),
+    /// Print a shell completion script
+    Completions { shell: completions::Shell },
+    /// List submodule paths for shell completion
+    #[clap(name = "__complete", hide = true)]
+    Complete,
+    /// List the commits a submodule has pointed to over the superproject's
+    /// history, oldest first
+    ListCommits {
+        path: Utf8PathBuf,
+        /// Print the commits as JSON
+        #[clap(long)]
+        json: bool,
+        /// Only list this many of the most recent changes
+        #[clap(long, short = 'n')]
+        limit: Option<usize>,
+    },
+    /// Show the commits a submodule has pointed to like a reflog, most recent
+    /// first, with the superproject commit that recorded each one
+    ReflogSubmodule {
+        path: Utf8PathBuf,
+        /// Print the entries as JSON
+        #[clap(long)]
+        json: bool,
+        /// Only show this many of the most recent entries
+        #[clap(long, short = 'n')]
+        limit: Option<usize>,
+    },
+    /// Set the URL that submodules push to, leaving the one they fetch from
+    SetPushurl(SetPushurlArgs),
+    /// Run a shell command in each initialized submodule
+    Foreach(ForeachArgs),
+}
+
+// Opens the repository containing `cwd`, unless `--git-dir` and `--work-tree`
+// were given, which `main` passes on through the environment so that they
+// apply to gix and git alike.
+fn discover(cwd: &Utf8Path) -> Result<gix::Repository, anyhow::Error> {
+    Ok(gix::ThreadSafeRepository::discover_with_environment_overrides(cwd)?.to_thread_local())
+}
+
+// Set by `--no-shell-out`.
+static NO_SHELL_OUT: AtomicBool = AtomicBool::new(false);
+
+// Finds the git binary to run for `operation`, unless `--no-shell-out` was
+// given.
+fn git_binary(operation: &str) -> Result<PathBuf, anyhow::Error> {
+    if NO_SHELL_OUT.load(Ordering::Relaxed) {
+        anyhow::bail!(
+            "{} needs the git binary, which --no-shell-out rules out",
+            operation
+        );
+    }
+    Ok(which::which("git")?)
+}
+
+// Creates a command that runs inside a submodule. It mustn't inherit the
+// `GIT_DIR` and `GIT_WORK_TREE` of the superproject.
+fn submodule_command(program: impl AsRef<std::ffi::OsStr>, work_dir: &Path) -> Command {
+    let mut command = Command::new(program);
+    command
+        .current_dir(work_dir)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE");
+    command
+}
+
+// Warns about submodules that authenticate differently than the URL the
+// superproject was cloned from, since cloning those often fails.
+fn warn_auth_mismatches(
+    repo: &gix::Repository,
+    superproject_url: &str,
+) -> Result<(), anyhow::Error> {
+    let Some(modules) = url::raw_modules(repo)? else {
+        return Ok(());
+    };
+    let Some(expected) = Url::try_from(superproject_url)
+        .ok()
+        .as_ref()
+        .and_then(url::Auth::of)
+    else {
+        return Ok(());
+    };
+    for mismatch in url::auth_mismatches(&modules, expected) {
+        eprintln!(
+            "{} submodule {} uses {} ({}) but the superproject was cloned over {}",
+            "warning:".yellow().bold(),
+            format_name(&mismatch.name).bold(),
+            mismatch.auth.name(),
+            mismatch.url.as_str().dimmed(),
+            expected.name()
+        );
+        if let Some(host) = &mismatch.host {
+            eprintln!(
+                "  {}",
+                format!(
+                    "if it can't be cloned, rewrite its URL with `git config --global url.\"{}\".insteadOf \"{}\"`, then run `git submodule sync` and `gm init`",
+                    expected.prefix(host),
+                    mismatch.auth.prefix(host)
+                )
+                .dimmed()
+            );
+        }
+    }
+    Ok(())
+}
+
+// Sets `section.subsection.key = value` for each of `values` in the config
+// file at `path`, keeping the rest of the file as it is.
+fn set_config(
+    path: &Path,
+    values: &[(&str, Option<&BStr>, &'static str, &str)],
+) -> Result<(), anyhow::Error> {
+    let mut config =
+        gix::config::File::from_path_no_includes(path.to_path_buf(), gix::config::Source::Local)?;
+    for (section, subsection, key, value) in values {
+        config.set_raw_value_by(section, *subsection, *key, *value)?;
+    }
+    std::fs::write(path, config.to_bstring())?;
+    Ok(())
+}
+
+// Works out which repository a line of `git clone --recursive` output is
+// about. `Cloning into '...'` starts a new repository, which the lines after
+// it are about until the next one; lines naming a submodule are about that
+// submodule only. Returns `None` if it can't tell.
+fn output_prefix(line: &str, current: &mut Option<String>) -> Option<String> {
+    if let Some(path) = line
+        .strip_prefix("Cloning into '")
+        .and_then(|rest| rest.strip_suffix("'..."))
+    {
+        *current = Some(format_name(path.trim_end_matches('/')).to_string());
+    }
+    let named = line
+        .strip_prefix("Submodule path '")
+        .or_else(|| line.strip_prefix("Submodule '"))
+        .and_then(|rest| rest.split_once('\''))
+        .map(|(name, _)| format_name(name).to_string());
+    named.or_else(|| current.clone())
+}
+
+// Prints the lines read from `reader`, each prefixed with the name `prefix`
+// returns for it, if any.
+fn print_prefixed(
+    reader: impl std::io::Read,
+    prefix: &(dyn Fn(&str) -> Option<String> + Sync),
+    is_stderr: bool,
+) -> std::io::Result<()> {
+    use std::io::BufRead;
+    for line in std::io::BufReader::new(reader).split(b'\n') {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\r');
+        let line = match prefix(line) {
+            Some(name) => format!("{} {}", format!("[{}]", name).dimmed(), line),
+            None => line.to_string(),
+        };
+        match is_stderr {
+            true => eprintln!("{}", line),
+            false => println!("{}", line),
+        }
+    }
+    Ok(())
+}
+
+// Runs `command` with each line of its output prefixed by `print_prefixed`.
+fn run_prefixed(
+    command: &mut Command,
+    prefix: &(dyn Fn(&str) -> Option<String> + Sync),
+) -> Result<std::process::ExitStatus, anyhow::Error> {
+    let mut child = command
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    std::thread::scope(|scope| {
+        let stderr = scope.spawn(|| print_prefixed(stderr, prefix, true));
+        print_prefixed(stdout, prefix, false)?;
+        stderr.join().expect("printing doesn't panic")
+    })?;
+    Ok(child.wait()?)
+}
+
+// Finds the git repositories in `dir` and the directories under it, without
+// looking for more inside the ones found. Directories under `dir` that can't
+// be read are skipped with a warning.
+fn find_repos(dir: &Path, repos: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if dir.join(".git").exists() {
+        repos.push(dir.to_path_buf());
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            if let Err(err) = find_repos(&entry.path(), repos) {
+                eprintln!(
+                    "{} {} {}",
+                    "skipping".yellow().bold(),
+                    entry.path().display(),
+                    format!("({})", err).dimmed()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Runs `gm` again in every repository under `dir`, with the same arguments
+// minus `--repos`, and prefixes the output with the repository's path.
+fn run_in_repos(dir: &Utf8Path) -> Result<(), anyhow::Error> {
+    let mut repos = Vec::new();
+    find_repos(dir.as_std_path(), &mut repos)?;
+    repos.sort();
+
+    // Only the global `--repos` before the subcommand is dropped, and
+    // everything from the subcommand on is passed as is. `--repos` can't be
+    // combined with the other global options that take a value, so the first
+    // argument that isn't an option is the subcommand.
+    let mut args = Vec::new();
+    let mut raw_args = std::env::args_os().skip(1);
+    while let Some(arg) = raw_args.next() {
+        let text = arg.to_string_lossy();
+        if text == "--repos" {
+            raw_args.next();
+            continue;
+        }
+        if text.starts_with("--repos=") {
+            continue;
+        }
+        let is_subcommand = !text.starts_with('-');
+        args.push(arg);
+        if is_subcommand {
+            args.extend(raw_args);
+            break;
+        }
+    }
+    let gm = std::env::current_exe()?;
+
+    let mut failed = Vec::new();
+    for repo in &repos {
+        let name = match repo.strip_prefix(dir) {
+            Ok(name) if !name.as_os_str().is_empty() => name.display().to_string(),
+            _ => repo.display().to_string(),
+        };
+        let mut command = Command::new(&gm);
+        command.arg("--cwd").arg(repo).args(&args);
+        // Keep the colors, which the output being piped would turn off.
+        if std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+            command.env("CLICOLOR_FORCE", "1");
+        }
+        let status = run_prefixed(&mut command, &|_| Some(name.clone()))?;
+        if !status.success() {
+            failed.push(name);
+        }
+    }
+
+    println!(
+        "{} {}",
+        "ran in".bold(),
+        match repos.len() {
+            1 => "1 repository".to_string(),
+            count => format!("{} repositories", count),
+        }
+    );
+    if !failed.is_empty() {
+        println!("{}", "failed in:".red().bold());
+        for name in failed {
+            println!("  {}", name);
+        }
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+// Whether `gm clone` recurses into submodules. The flags win over
+// `GM_RECURSE`, which wins over `submodule.recurse` from the user's
+// configuration, which is only read if it's needed. These only matter if
+// they're `false` since recursing is the default.
+fn clone_recursion(
+    recursive: bool,
+    no_recursive: bool,
+    env: Option<&str>,
+    config: impl FnOnce() -> Result<Option<bool>, anyhow::Error>,
+) -> Result<bool, anyhow::Error> {
+    match (recursive, no_recursive, env) {
+        (true, _, _) => Ok(true),
+        (_, true, _) => Ok(false),
+        (_, _, Some(value)) => match gix::config::Boolean::try_from(value.as_bytes().as_bstr()) {
+            Ok(gix::config::Boolean(recurse)) => Ok(recurse),
+            Err(_) => anyhow::bail!("GM_RECURSE must be true or false, not {}", value),
+        },
+        _ => Ok(config()?.unwrap_or(true)),
+    }
+}
+
+// Says that a submodule is left out of a command, and why.
+fn print_skipped(name: &ColoredString, reason: &str) {
+    println!(
+        "{} {} {}",
+        "skipping".dimmed(),
+        name,
+        format!("({})", reason).dimmed()
+    );
 }
 
 // Tries to use the last component of the path as the name of the submodule.
@@ -57,15 +470,227 @@ fn display_name(submodule: &gix::Submodule) -> Result<ColoredString, anyhow::Err
     }
 }
 
-fn display_change(change: &Item) -> Result<(), anyhow::Error> {
+// Returns `path` relative to `base`, going up with `..` where needed. Both
+// paths need to be absolute.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let path = path.components().collect::<Vec<_>>();
+    let base = base.components().collect::<Vec<_>>();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &path[common..] {
+        relative.push(component);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+// Resolves a path given on the command line to an absolute path, following
+// symlinks where the path exists.
+fn absolute_path(path: &Path) -> Result<PathBuf, anyhow::Error> {
+    Ok(path.canonicalize().or_else(|_| std::path::absolute(path))?)
+}
+
+// Makes `path` absolute and drops `.` and `..` components without resolving
+// symlinks.
+fn lexical_absolute(path: &Path) -> Result<PathBuf, anyhow::Error> {
+    let mut normalized = PathBuf::new();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    Ok(normalized)
+}
+
+// Returns `path` relative to the worktree at `work_dir`, or `None` if it's
+// outside of it. The paths are compared before resolving symlinks too, as a
+// path through a symlinked submodule directory would otherwise resolve to
+// somewhere outside the worktree.
+fn worktree_relative(path: &Path, work_dir: &Path) -> Result<Option<PathBuf>, anyhow::Error> {
+    let lexical = lexical_absolute(path)?;
+    for base in [lexical_absolute(work_dir)?, absolute_path(work_dir)?] {
+        if let Ok(relative) = lexical.strip_prefix(&base) {
+            return Ok(Some(relative.to_path_buf()));
+        }
+    }
+    Ok(absolute_path(path)?
+        .strip_prefix(absolute_path(work_dir)?)
+        .ok()
+        .map(Path::to_path_buf))
+}
+
+// Returns the submodules sorted by name. If any paths are given, only the
+// submodules at, under or containing one of those paths are returned.
+fn select_submodules<'repo>(
+    repo: &'repo gix::Repository,
+    cwd: &Utf8Path,
+    paths: &[Utf8PathBuf],
+) -> Result<Vec<gix::Submodule<'repo>>, anyhow::Error> {
+    let Some(submodules) = repo.submodules()? else {
+        return Ok(Vec::new());
+    };
+    let submodules = submodules.sorted_by(|a, b| a.name().cmp(b.name()));
+    if paths.is_empty() {
+        return Ok(submodules.collect());
+    }
+
+    let Some(work_dir) = repo.work_dir() else {
+        anyhow::bail!("cannot select submodules by path in a bare repository");
+    };
+    let prefixes = paths
+        .iter()
+        .map(
+            |path| match worktree_relative(cwd.join(path).as_std_path(), work_dir)? {
+                Some(prefix) => Ok(prefix),
+                None => anyhow::bail!("{} is outside the repository", path),
+            },
+        )
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    let mut matched = vec![false; prefixes.len()];
+    let mut selected = Vec::new();
+    for submodule in submodules {
+        let submodule_path = gix::path::from_bstr(submodule.path()?).into_owned();
+        let mut is_selected = false;
+        for (prefix, matched) in prefixes.iter().zip(matched.iter_mut()) {
+            if submodule_path.starts_with(prefix) || prefix.starts_with(&submodule_path) {
+                *matched = true;
+                is_selected = true;
+            }
+        }
+        if is_selected {
+            selected.push(submodule);
+        }
+    }
+
+    if let Some((path, _)) = paths.iter().zip(matched).find(|(_, matched)| !matched) {
+        anyhow::bail!("{} did not match any submodule", path);
+    }
+
+    Ok(selected)
+}
+
+// Selects the one submodule at, under or containing `path`.
+fn single_submodule<'repo>(
+    repo: &'repo gix::Repository,
+    cwd: &Utf8Path,
+    path: &Utf8Path,
+) -> Result<gix::Submodule<'repo>, anyhow::Error> {
+    let mut submodules = select_submodules(repo, cwd, &[path.to_path_buf()])?;
+    match submodules.len() {
+        0 => anyhow::bail!("no submodules found"),
+        1 => Ok(submodules.remove(0)),
+        _ => anyhow::bail!("{} matches more than one submodule", path),
+    }
+}
+
+// A submodule's worktree is missing if its repository exists but the
+// directory the gitlink points at is gone or empty.
+fn is_worktree_missing(submodule: &gix::Submodule) -> Result<bool, anyhow::Error> {
+    if !submodule.state()?.repository_exists {
+        return Ok(false);
+    }
+    let work_dir = submodule.work_dir()?;
+    Ok(match std::fs::read_dir(&work_dir) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(_) => true,
+    })
+}
+
+// Runs `f` on every item using up to `jobs` threads, returning the results in
+// the same order as `items` regardless of which finished first.
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: Option<usize>,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let jobs = jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |jobs| jobs.get()))
+        .clamp(1, items.len().max(1));
+    let queue = Mutex::new(items.iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let Some((idx, item)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap().push((idx, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+// Whether only the executable bit of a file changed, which is usually noise
+// on filesystems that don't track it, e.g. across the Windows/WSL boundary.
+fn is_mode_only(change: &Item) -> bool {
+    matches!(
+        change,
+        Item::Modification {
+            status: EntryStatus::Change(Change::Modification {
+                executable_bit_changed: true,
+                content_change: None,
+                ..
+            }),
+            ..
+        }
+    )
+}
+
+// Prints a changed file of a submodule. `format_path` turns the path relative
+// to the submodule into the path that should be shown.
+fn display_change(
+    change: &Item,
+    format_path: &dyn Fn(&BStr) -> String,
+) -> Result<(), anyhow::Error> {
     match change {
         Item::Modification {
             rela_path, status, ..
         } => {
+            if is_mode_only(change) {
+                println!(
+                    "    {} {}",
+                    format_path(rela_path.as_bstr()).as_str().dimmed(),
+                    "mode".dimmed()
+                );
+                return Ok(());
+            }
             let name = match status {
-                EntryStatus::Conflict(_) => rela_path.to_str_lossy().bold().red(),
+                EntryStatus::Conflict(_) => format_path(rela_path.as_bstr()).as_str().bold().red(),
                 EntryStatus::Change(_) | EntryStatus::IntentToAdd => {
-                    rela_path.to_str_lossy().bold().yellow()
+                    format_path(rela_path.as_bstr()).as_str().bold().yellow()
                 }
                 EntryStatus::NeedsUpdate(_) => return Ok(()),
             };
@@ -73,7 +698,10 @@ fn display_change(change: &Item) -> Result<(), anyhow::Error> {
         }
         Item::DirectoryContents { entry, .. } => {
             // We're assuming it's untracked
-            println!("    {}", entry.rela_path.to_str_lossy().red());
+            println!(
+                "    {}",
+                format_path(entry.rela_path.as_bstr()).as_str().red()
+            );
         }
         Item::Rewrite {
             source:
@@ -85,8 +713,10 @@ fn display_change(change: &Item) -> Result<(), anyhow::Error> {
         } => {
             println!(
                 "    {} -> {}",
-                source_rela_path.to_str_lossy().bold(),
-                dirwalk_entry.rela_path.to_str_lossy().bold()
+                format_path(source_rela_path.as_bstr()).as_str().bold(),
+                format_path(dirwalk_entry.rela_path.as_bstr())
+                    .as_str()
+                    .bold()
             );
         }
         Item::Rewrite {
@@ -100,8 +730,12 @@ fn display_change(change: &Item) -> Result<(), anyhow::Error> {
         } => {
             println!(
                 "    {} -> {}",
-                source_dirwalk_entry.rela_path.to_str_lossy().bold(),
-                dirwalk_entry.rela_path.to_str_lossy().bold()
+                format_path(source_dirwalk_entry.rela_path.as_bstr())
+                    .as_str()
+                    .bold(),
+                format_path(dirwalk_entry.rela_path.as_bstr())
+                    .as_str()
+                    .bold()
             );
         }
     }
@@ -111,92 +745,189 @@ fn display_change(change: &Item) -> Result<(), anyhow::Error> {
 
 fn main() -> Result<(), anyhow::Error> {
     let args = Args::parse();
-    let cwd = if let Some(cwd) = args.cwd {
-        cwd
-    } else {
-        std::env::current_dir()?.try_into()?
+    let has_cwd = args.cwd.is_some();
+    let cwd = match args.cwd {
+        // Editor integrations tend to pass the path of the current file, so
+        // run from the directory containing it.
+        Some(cwd) if cwd.is_file() => match cwd.parent() {
+            Some(parent) if !parent.as_str().is_empty() => parent.to_path_buf(),
+            _ => Utf8PathBuf::from("."),
+        },
+        Some(cwd) => cwd,
+        None => std::env::current_dir()?.try_into()?,
+    };
+
+    if let Some(repos) = &args.repos {
+        if matches!(args.command, Subcommand::Clone { .. }) {
+            anyhow::bail!("--repos can't be used with `gm clone`");
+        }
+        return run_in_repos(&cwd.join(repos));
+    }
+    NO_SHELL_OUT.store(args.no_shell_out, Ordering::Relaxed);
+    if let Some(git_dir) = &args.git_dir {
+        if matches!(args.command, Subcommand::Clone { .. }) {
+            anyhow::bail!("--git-dir and --work-tree can't be used with `gm clone`");
+        }
+        std::env::set_var("GIT_DIR", absolute_path(cwd.join(git_dir).as_std_path())?);
+    }
+    // Without an explicit `--cwd`, paths are taken relative to the worktree.
+    let cwd = match &args.work_tree {
+        Some(work_tree) => {
+            let work_tree = absolute_path(cwd.join(work_tree).as_std_path())?;
+            std::env::set_var("GIT_WORK_TREE", &work_tree);
+            match has_cwd {
+                true => cwd,
+                false => work_tree.try_into()?,
+            }
+        }
+        None => cwd,
     };
 
     match args.command {
-        Subcommand::Clone { url, path } => {
-            let git = which::which("git")?;
+        Subcommand::Clone {
+            url,
+            path,
+            no_checkout,
+            separate_git_dir,
+            prefix_output,
+            recursive,
+            no_recursive,
+        } => {
+            let git = git_binary("`gm clone`")?;
             let mut command = Command::new(git);
 
-            command
-                .arg("clone")
-                .arg("--recursive")
-                .arg(&url)
-                .current_dir(&cwd);
+            let recursive = clone_recursion(
+                recursive,
+                no_recursive,
+                std::env::var("GM_RECURSE").ok().as_deref(),
+                || {
+                    let mut config = gix::config::File::from_globals()?;
+                    config.append(gix::config::File::from_environment_overrides()?);
+                    Ok(config.boolean("submodule.recurse").transpose()?)
+                },
+            )?;
+
+            command.arg("clone");
+            if recursive {
+                command.arg("--recursive");
+            }
+            if no_checkout {
+                command.arg("--no-checkout");
+            }
+            if let Some(separate_git_dir) = &separate_git_dir {
+                command.arg("--separate-git-dir").arg(separate_git_dir);
+            }
+            command.arg(&url).current_dir(&cwd);
 
             if let Some(path) = &path {
                 command.arg(path);
             }
 
-            let status = command.spawn()?.wait()?;
+            let parsed_url = Url::try_from(url.as_str())?;
+            let repo_path = match path {
+                Some(path) => Some(path),
+                None if parsed_url.host() == Some("github.com") => {
+                    let url_path = parsed_url.path.to_string();
+                    url_path
+                        .rsplit_once('/')
+                        .map(|(_, path)| Utf8PathBuf::from(path.trim_end_matches(".git")))
+                }
+                None => None,
+            };
+
+            let current = Mutex::new(None);
+            let status = match prefix_output {
+                true => run_prefixed(&mut command, &|line| {
+                    output_prefix(line, &mut current.lock().unwrap())
+                })?,
+                false => command.spawn()?.wait()?,
+            };
             if !status.success() {
+                // Submodule clones failing on authentication still leave the
+                // superproject behind, and the mismatch is likely the reason.
+                if let Some(repo) = repo_path
+                    .as_ref()
+                    .and_then(|path| gix::open(cwd.join(path)).ok())
+                {
+                    warn_auth_mismatches(&repo, &url)?;
+                }
                 std::process::exit(status.code().unwrap_or(1));
             }
 
-            let url = Url::try_from(url.as_str())?;
-            let repo_path = match path {
-                Some(path) => path,
-                None if url.host() == Some("github.com") => {
-                    let url_path = url.path.to_string();
-                    let Some((_, path)) = url_path.rsplit_once('/') else {
-                        return Ok(());
-                    };
-                    let path = path.trim_end_matches(".git");
-                    Utf8PathBuf::from(path)
-                }
-                None => {
-                    debug!("cannot determine path from url");
-                    return Ok(());
-                }
+            let Some(repo_path) = repo_path else {
+                debug!("cannot determine path from url");
+                return Ok(());
             };
-
             let abs_repo_path = cwd.join(repo_path);
 
             let repo = gix::discover(abs_repo_path)?;
+            warn_auth_mismatches(&repo, &url)?;
             let Some(submodules) = repo.submodules()? else {
                 return Ok(());
             };
+
+            // Nothing was cloned besides the superproject, so just list the
+            // submodules. Without a checkout there's no `.gitmodules` in the
+            // worktree, and they come from the one in the fetched `HEAD` tree.
+            if no_checkout || !recursive {
+                for submodule in submodules.sorted_by(|a, b| a.name().cmp(b.name())) {
+                    println!(
+                        "{} {} {} {}",
+                        "found".bold(),
+                        display_name(&submodule)?,
+                        "at".bold(),
+                        submodule.path()?.to_str_lossy().dimmed().bold()
+                    );
+                }
+                let hint = match no_checkout {
+                    true => "run `git checkout` and then `gm init` to check out the submodules",
+                    false => "run `gm init` to check out the submodules",
+                };
+                println!("{}", hint.dimmed());
+                return Ok(());
+            }
+
+            let mut declared = 0;
+            let mut initialized = 0;
             for submodule in submodules.sorted_by(|a, b| a.name().cmp(b.name())) {
-                println!(
-                    "{} {} {} {}",
-                    "initialized".bold(),
-                    display_name(&submodule)?,
-                    "at".bold(),
-                    submodule.path()?.to_str_lossy().dimmed().bold()
-                );
+                declared += 1;
+                let state = submodule.state()?;
+                if state.repository_exists && state.worktree_checkout {
+                    initialized += 1;
+                    println!(
+                        "{} {} {} {}",
+                        "initialized".bold(),
+                        display_name(&submodule)?,
+                        "at".bold(),
+                        submodule.path()?.to_str_lossy().dimmed().bold()
+                    );
+                } else {
+                    println!(
+                        "{} {} {} {}",
+                        "failed to initialize".red().bold(),
+                        display_name(&submodule)?,
+                        "at".bold(),
+                        submodule.path()?.to_str_lossy().dimmed().bold()
+                    );
+                }
             }
-        }
-        Subcommand::Init => {
-            let git = which::which("git")?;
-            Command::new(&git)
-                .arg("submodule")
-                .arg("init")
-                .current_dir(&cwd)
-                .spawn()?
-                .wait()?;
 
-            Command::new(&git)
-                .arg("submodule")
-                .arg("update")
-                .current_dir(&cwd)
-                .spawn()?
-                .wait()?;
-        }
-        Subcommand::Rm { path } => {
-            let git = which::which("git")?;
-            Command::new(&git)
-                .arg("rm")
-                .arg(path)
-                .current_dir(&cwd)
-                .spawn()?
-                .wait()?;
+            // A recursive clone can leave submodules behind without failing,
+            // so compare against what `.gitmodules` declares.
+            if initialized != declared {
+                eprintln!(
+                    "{} only {} of {} submodules declared in .gitmodules were initialized",
+                    "warning:".yellow().bold(),
+                    initialized,
+                    declared
+                );
+            }
         }
+        Subcommand::Add(args) => add::add(&cwd, args)?,
+        Subcommand::Init { jobs, recursive } => init::init(&cwd, jobs, recursive)?,
+        Subcommand::Rm(args) => rm::rm(&cwd, args)?,
         Subcommand::Pull { args } => {
-            let git = which::which("git")?;
+            let git = git_binary("`gm pull`")?;
             Command::new(&git)
                 .arg("pull")
                 .args(args)
@@ -213,51 +944,184 @@ fn main() -> Result<(), anyhow::Error> {
                 .spawn()?
                 .wait()?;
         }
-        Subcommand::Ls => {
-            let repo = gix::discover(cwd)?;
+        Subcommand::Ls {
+            json,
+            schema_version,
+            emit_relative_urls,
+        } => {
+            let repo = discover(&cwd)?;
+            let modules = url::raw_modules(&repo)?;
+            if json {
+                let entries = select_submodules(&repo, &cwd, &[])?
+                    .iter()
+                    .map(|submodule| {
+                        let state = submodule.state()?;
+                        Ok(json::LsEntry {
+                            name: submodule.name().to_str_lossy().into_owned(),
+                            path: submodule.path()?.to_str_lossy().into_owned(),
+                            url: url::submodule_url(
+                                &repo,
+                                modules.as_ref(),
+                                submodule,
+                                emit_relative_urls.unwrap_or(UrlForm::Raw),
+                            )?,
+                            initialized: state.repository_exists && state.worktree_checkout,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, anyhow::Error>>()?;
+                return json::print(entries, schema_version);
+            }
+
             let Some(submodules) = repo.submodules()? else {
                 println!("No submodules found");
                 return Ok(());
             };
             for submodule in submodules.sorted_by(|a, b| a.name().cmp(b.name())) {
-                println!(
+                print!(
                     "{} {}",
                     display_name(&submodule)?,
                     submodule.path()?.to_str_lossy().dimmed()
                 );
+                if let Some(form) = emit_relative_urls {
+                    print!(
+                        " {}",
+                        url::submodule_url(&repo, modules.as_ref(), &submodule, form)?.dimmed()
+                    );
+                }
+                println!();
             }
         }
-        Subcommand::Status => {
-            let repo = gix::discover(cwd)?;
-            let Some(submodules) = repo.submodules()? else {
-                println!("No submodules found");
+        Subcommand::Status(args) => status::status(&cwd, args)?,
+        Subcommand::Info(args) => info::info(&cwd, args)?,
+        Subcommand::Doctor => doctor::doctor(&cwd)?,
+        Subcommand::Export(args) => export::export(&cwd, args)?,
+        Subcommand::Reset(args) => reset::reset(&cwd, args)?,
+        Subcommand::Update(args) => update::update(&cwd, args)?,
+        Subcommand::Gc(args) => gc::gc(&cwd, args)?,
+        Subcommand::Completions { shell } => {
+            let command = Args::command();
+            let subcommands = command
+                .get_subcommands()
+                .filter(|subcommand| !subcommand.is_hide_set())
+                .map(|subcommand| subcommand.get_name())
+                .collect::<Vec<_>>();
+            completions::print_script(shell, &subcommands);
+        }
+        Subcommand::Complete => completions::print_submodule_paths(&cwd),
+        Subcommand::ListCommits { path, json, limit } => {
+            let repo = discover(&cwd)?;
+            let submodule = single_submodule(&repo, &cwd, &path)?;
+            let transitions = history::pointer_history(&repo, submodule.path()?.as_ref(), limit)?;
+
+            if json {
+                let entries = transitions
+                    .iter()
+                    .map(json::Transition::new)
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&entries)?);
                 return Ok(());
-            };
-            for submodule in submodules.sorted_by(|a, b| a.name().cmp(b.name())) {
-                let status = submodule.status(Ignore::None, false)?;
+            }
+
+            for transition in transitions {
                 println!(
-                    "{} {} {}",
-                    display_name(&submodule)?,
-                    submodule.path()?.to_str_lossy().dimmed(),
-                    match status.is_dirty() {
-                        Some(true) => "dirty".yellow().bold(),
-                        Some(false) => "clean".green().bold(),
-                        None if submodule.state()?.repository_exists => "unknown".bold(),
-                        None => "uninitialized".dimmed().bold(),
-                    }
+                    "{} {} {} {} {}",
+                    transition
+                        .change
+                        .time
+                        .format(gix::date::time::format::SHORT)
+                        .dimmed(),
+                    transition
+                        .change
+                        .commit
+                        .to_hex_with_len(7)
+                        .to_string()
+                        .yellow(),
+                    "->".dimmed(),
+                    match transition.gitlink {
+                        Some(id) => id.to_hex_with_len(7).to_string().bold(),
+                        None => "removed".red().bold(),
+                    },
+                    transition.change.author.dimmed()
                 );
-                if let Some(changes) = status.changes {
-                    if !changes.is_empty() {
-                        println!("  changes:");
-                    }
-
-                    for change in changes {
-                        display_change(&change)?;
-                    }
-                }
             }
         }
+        Subcommand::ReflogSubmodule { path, json, limit } => {
+            let repo = discover(&cwd)?;
+            let submodule = single_submodule(&repo, &cwd, &path)?;
+            let name = display_name(&submodule)?;
+            let mut transitions =
+                history::pointer_history(&repo, submodule.path()?.as_ref(), limit)?;
+            // Like a reflog, the most recent entry comes first.
+            transitions.reverse();
+
+            if json {
+                let entries = transitions
+                    .iter()
+                    .map(json::Transition::new)
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+
+            for (idx, transition) in transitions.iter().enumerate() {
+                println!(
+                    "{} {}: {} {} {}",
+                    match transition.gitlink {
+                        Some(id) => id.to_hex_with_len(7).to_string().yellow(),
+                        None => "removed".red(),
+                    },
+                    format!("{}@{{{}}}", name, idx).bold(),
+                    transition.change.commit.to_hex_with_len(7),
+                    transition
+                        .change
+                        .time
+                        .format(gix::date::time::format::SHORT)
+                        .dimmed(),
+                    transition.change.author.dimmed()
+                );
+            }
+        }
+        Subcommand::SetPushurl(args) => set_pushurl::set_pushurl(&cwd, args)?,
+        Subcommand::Foreach(args) => foreach::foreach(&cwd, args)?,
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::clone_recursion;
+
+    fn unset() -> Result<Option<bool>, anyhow::Error> {
+        Ok(None)
+    }
+
+    #[test]
+    fn clone_recursion_defaults_to_recursing() {
+        assert!(clone_recursion(false, false, None, unset).unwrap());
+    }
+
+    #[test]
+    fn clone_recursion_flags_win() {
+        let config = || Ok(Some(false));
+        assert!(clone_recursion(true, false, Some("false"), config).unwrap());
+        let config = || Ok(Some(true));
+        assert!(!clone_recursion(false, true, Some("true"), config).unwrap());
+        // An invalid `GM_RECURSE` or config doesn't matter once a flag is given.
+        assert!(!clone_recursion(false, true, Some("maybe"), || anyhow::bail!("bad")).unwrap());
+    }
+
+    #[test]
+    fn clone_recursion_env_wins_over_config() {
+        assert!(!clone_recursion(false, false, Some("false"), || Ok(Some(true))).unwrap());
+        assert!(clone_recursion(false, false, Some("yes"), || Ok(Some(false))).unwrap());
+        assert!(!clone_recursion(false, false, Some("0"), unset).unwrap());
+        assert!(clone_recursion(false, false, Some("maybe"), unset).is_err());
+    }
+
+    #[test]
+    fn clone_recursion_falls_back_to_config() {
+        assert!(!clone_recursion(false, false, None, || Ok(Some(false))).unwrap());
+        assert!(clone_recursion(false, false, None, || Ok(Some(true))).unwrap());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    discover, display_name, git_binary, print_skipped, select_submodules, submodule_command,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use gix::submodule::config::Branch;
+
+#[derive(clap::Args)]
+pub struct ResetArgs {
+    paths: Vec<Utf8PathBuf>,
+    /// Check out the submodule's configured branch at the recorded commit
+    /// instead of detaching HEAD
+    #[clap(long)]
+    to_branch: bool,
+}
+
+pub fn reset(cwd: &Utf8Path, args: ResetArgs) -> Result<(), anyhow::Error> {
+    let git = git_binary("`gm reset`")?;
+    let repo = discover(cwd)?;
+    let mut failed = false;
+    for submodule in select_submodules(&repo, cwd, &args.paths)? {
+        let name = display_name(&submodule)?;
+        if !submodule.state()?.worktree_checkout {
+            print_skipped(&name, "uninitialized");
+            continue;
+        }
+        let Some(recorded) = submodule.index_id()? else {
+            print_skipped(&name, "not in index");
+            continue;
+        };
+
+        let mut command = submodule_command(&git, &submodule.work_dir()?);
+        command.arg("checkout").arg("--quiet");
+
+        let branch = if args.to_branch {
+            let branch = match submodule.branch()? {
+                Some(Branch::Name(branch)) => branch.to_string(),
+                Some(Branch::CurrentInSuperproject) => match repo.head_name()? {
+                    Some(head) => head.shorten().to_string(),
+                    None => {
+                        print_skipped(&name, "superproject HEAD is detached");
+                        continue;
+                    }
+                },
+                None => {
+                    print_skipped(&name, "no branch configured");
+                    continue;
+                }
+            };
+            command.arg("-B").arg(&branch).arg(recorded.to_string());
+            Some(branch)
+        } else {
+            command.arg("--detach").arg(recorded.to_string());
+            None
+        };
+
+        if !command.spawn()?.wait()?.success() {
+            println!("{} {}", "failed to reset".red().bold(), name);
+            failed = true;
+            continue;
+        }
+
+        match branch {
+            Some(branch) => println!(
+                "{} {} {} {} {} {}",
+                "reset".bold(),
+                name,
+                "to".bold(),
+                recorded.to_hex_with_len(7).to_string().dimmed(),
+                "on".bold(),
+                branch.green()
+            ),
+            None => println!(
+                "{} {} {} {}",
+                "reset".bold(),
+                name,
+                "to".bold(),
+                recorded.to_hex_with_len(7).to_string().dimmed()
+            ),
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
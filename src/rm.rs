@@ -0,0 +1,149 @@
+use crate::{discover, display_name, git_binary, single_submodule};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use gix::bstr::{BStr, ByteSlice};
+use gix::submodule::config::Ignore;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(clap::Args)]
+pub struct RmArgs {
+    path: Utf8PathBuf,
+    /// Only remove the submodule from the index, leaving its worktree
+    #[clap(long)]
+    cached: bool,
+    /// Leave the submodule's section in `.gitmodules`
+    #[clap(long, conflicts_with = "use_git_binary")]
+    keep_gitmodules: bool,
+    /// Run `git rm` instead of removing the submodule with `gm` itself
+    #[clap(long)]
+    use_git_binary: bool,
+}
+
+// Removes the `submodule.<name>` section from the config file at `path`.
+// Returns whether there was one.
+fn remove_section(
+    path: &Path,
+    source: gix::config::Source,
+    name: &BStr,
+) -> Result<bool, anyhow::Error> {
+    let mut config = gix::config::File::from_path_no_includes(path.to_path_buf(), source)?;
+    if config.remove_section("submodule", Some(name)).is_none() {
+        return Ok(false);
+    }
+    std::fs::write(path, config.to_bstring())?;
+    Ok(true)
+}
+
+// Removes `dir` and then its parents up to `toplevel` for as long as they're
+// left empty, like `git rm`.
+fn remove_worktree(dir: &Path, toplevel: &Path) -> std::io::Result<()> {
+    std::fs::remove_dir_all(dir)?;
+    for parent in dir.ancestors().skip(1) {
+        if parent == toplevel || std::fs::remove_dir(parent).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn rm(cwd: &Utf8Path, args: RmArgs) -> Result<(), anyhow::Error> {
+    if args.use_git_binary {
+        let git = git_binary("`gm rm --use-git-binary`")?;
+        let mut command = Command::new(&git);
+        command.arg("rm");
+        if args.cached {
+            command.arg("--cached");
+        }
+        let status = command.arg(&args.path).current_dir(cwd).status()?;
+        if !status.success() {
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        return Ok(());
+    }
+
+    let repo = discover(cwd)?;
+    let Some(toplevel) = repo.work_dir() else {
+        anyhow::bail!("cannot remove submodules from a bare repository");
+    };
+    let toplevel = crate::absolute_path(toplevel)?;
+    let submodule = single_submodule(&repo, cwd, &args.path)?;
+    let name = submodule.name().to_owned();
+    let path = submodule.path()?.into_owned();
+    let work_dir = crate::absolute_path(&submodule.work_dir()?)?;
+
+    // Like `git rm`, refuse to throw away anything that isn't saved
+    // elsewhere.
+    if !args.cached && submodule.state()?.worktree_checkout {
+        if work_dir.join(".git").is_dir() {
+            anyhow::bail!(
+                "{} has its git directory inside its worktree; run `git submodule absorbgitdirs` first, or pass --cached",
+                path
+            );
+        }
+        let has_changes = submodule
+            .status(Ignore::None, false)?
+            .changes
+            .is_some_and(|changes| changes.iter().any(|change| change.summary().is_some()));
+        if has_changes {
+            anyhow::bail!(
+                "{} has local changes; commit or discard them first, or pass --cached",
+                path
+            );
+        }
+    }
+    let display_name = display_name(&submodule)?;
+
+    let mut index = repo.open_index()?;
+    if index.entry_by_path(path.as_ref()).is_none() {
+        anyhow::bail!("{} isn't in the index", path);
+    }
+    index.remove_entries(|_, entry_path, _| entry_path == path);
+
+    if !args.cached && work_dir.exists() {
+        remove_worktree(&work_dir, &toplevel)?;
+    }
+
+    // Stage the new `.gitmodules` too, so the removal can be committed as is.
+    let gitmodules = toplevel.join(".gitmodules");
+    if !args.keep_gitmodules
+        && gitmodules.is_file()
+        && remove_section(&gitmodules, gix::config::Source::Worktree, name.as_ref())?
+    {
+        let id = repo.write_blob_stream(std::fs::File::open(&gitmodules)?)?;
+        let stat = gix::index::entry::Stat::from_fs(
+            &gix::index::fs::Metadata::from_path_no_follow(&gitmodules)?,
+        )?;
+        if let Some(entry) = index.entry_mut_by_path_and_stage(
+            ".gitmodules".as_bytes().as_bstr(),
+            gix::index::entry::Stage::Unconflicted,
+        ) {
+            entry.id = id.detach();
+            entry.stat = stat;
+        }
+    }
+    // The cached trees no longer match the entries, and git would trust
+    // them over the entries.
+    index.write(gix::index::write::Options {
+        extensions: gix::index::write::Extensions::Given {
+            tree_cache: false,
+            end_of_index_entry: true,
+        },
+        ..Default::default()
+    })?;
+
+    remove_section(
+        &repo.common_dir().join("config"),
+        gix::config::Source::Local,
+        name.as_ref(),
+    )?;
+
+    println!(
+        "{} {} {}",
+        "removed".bold(),
+        display_name,
+        path.to_str_lossy().dimmed()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use crate::{discover, display_name, format_name, print_skipped, select_submodules, set_config};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use gix::bstr::ByteSlice;
+
+#[derive(clap::Args)]
+pub struct SetPushurlArgs {
+    /// The push URL. `%name%` is replaced with each submodule's name as
+    /// shown by `gm`, e.g. `git@github.com:me/%name%.git`
+    url: String,
+    /// Only change the submodules at, under or containing these paths
+    paths: Vec<Utf8PathBuf>,
+    /// The remote to change; defaults to each submodule's default push remote
+    #[clap(long)]
+    remote: Option<String>,
+}
+
+pub fn set_pushurl(cwd: &Utf8Path, args: SetPushurlArgs) -> Result<(), anyhow::Error> {
+    let repo = discover(cwd)?;
+    for submodule in select_submodules(&repo, cwd, &args.paths)? {
+        let name = display_name(&submodule)?;
+        let Some(sm_repo) = submodule.open()? else {
+            print_skipped(&name, "uninitialized");
+            continue;
+        };
+        let remote = match &args.remote {
+            Some(remote) => remote.clone(),
+            None => sm_repo
+                .remote_default_name(gix::remote::Direction::Push)
+                .map(|name| name.to_str_lossy().into_owned())
+                .unwrap_or_else(|| "origin".to_string()),
+        };
+        if sm_repo.find_remote(remote.as_str()).is_err() {
+            print_skipped(&name, &format!("no remote named {}", remote));
+            continue;
+        }
+        let pushurl = args
+            .url
+            .replace("%name%", format_name(&submodule.name().to_str_lossy()));
+        set_config(
+            &sm_repo.common_dir().join("config"),
+            &[("remote", Some(remote.as_str().into()), "pushurl", &pushurl)],
+        )?;
+        println!(
+            "{} {} {} {}",
+            name,
+            remote.as_str().dimmed(),
+            "pushes to".bold(),
+            pushurl.green()
+        );
+    }
+
+    Ok(())
+}
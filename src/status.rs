@@ -0,0 +1,978 @@
+use crate::diff;
+use crate::json::{self, State};
+use crate::url::{self, UrlForm};
+use crate::{
+    absolute_path, display_change, display_name, history, is_mode_only, is_worktree_missing,
+    relative_to, select_submodules, EXIT_CONFLICTS, EXIT_UNINITIALIZED,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::{ColoredString, Colorize};
+use gix::bstr::{BStr, BString, ByteSlice};
+use gix::status::index_worktree::iter::Item;
+use gix::status::plumbing::index_as_worktree::EntryStatus;
+use gix::submodule::config::{Branch, Ignore};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(clap::Args)]
+pub struct StatusArgs {
+    /// Only show submodules at, under or containing these paths
+    paths: Vec<Utf8PathBuf>,
+    /// Only show the submodule with this name, as written in `.gitmodules`.
+    /// Unlike paths, names are matched exactly and can be repeated
+    #[clap(long = "only", value_name = "NAME")]
+    only: Vec<String>,
+    /// Show the superproject commit that last moved each submodule pointer
+    #[clap(long)]
+    blame_pointer: bool,
+    /// Exit with code 3 if any submodule is uninitialized
+    #[clap(long)]
+    fail_on_uninitialized: bool,
+    /// Exit with code 4 if any submodule has conflicts, regardless of other
+    /// changes
+    #[clap(long)]
+    only_conflicts_exit_code: bool,
+    /// Report submodules whose worktree directory is missing or empty,
+    /// e.g. because it was moved without telling git
+    #[clap(long)]
+    detect_moved_dirs: bool,
+    /// Print the status as JSON
+    #[clap(long)]
+    json: bool,
+    /// Wrap the JSON output in an object with the schema version
+    #[clap(long, requires = "json")]
+    schema_version: bool,
+    /// Show each submodule's URL, with relative URLs either as written
+    /// (`raw`) or resolved against the superproject's remote (`resolved`)
+    #[clap(long, value_enum)]
+    emit_relative_urls: Option<UrlForm>,
+    /// Show paths relative to the current directory instead of the
+    /// repository root. Can be made the default with `gm.relativePaths`
+    #[clap(long)]
+    relative: bool,
+    /// Treat the submodule at this path as clean and don't list its changes,
+    /// e.g. for intentionally patched vendored code. Can be repeated, and
+    /// doesn't change the exit codes unless `--strict` is passed
+    #[clap(long, value_name = "PATH")]
+    assume_clean: Vec<Utf8PathBuf>,
+    /// Leave the submodules passed to `--assume-clean` out of the exit codes
+    /// of `--fail-on-uninitialized` and `--only-conflicts-exit-code` too
+    #[clap(long, requires = "assume_clean")]
+    strict: bool,
+    /// Print a single line with one character per submodule, followed by a
+    /// legend
+    #[clap(long, conflicts_with = "json")]
+    terse: bool,
+    /// Don't print the legend after `--terse` output, e.g. when it's already
+    /// been shown once
+    #[clap(long, requires = "terse")]
+    no_legend: bool,
+    /// Ignore files whose executable bit is the only change, like
+    /// `core.fileMode=false`
+    #[clap(long)]
+    ignore_filemode: bool,
+    /// Only list changed files modified within this long, e.g. `30m`, `1h`
+    /// or `2d`. Going by modification times, this is only approximate
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "json")]
+    changed_since: Option<Duration>,
+    /// Don't show whether the superproject itself has changes
+    #[clap(long)]
+    no_superproject: bool,
+    /// Show a diff under each modified file
+    #[clap(long, conflicts_with_all = ["json", "terse"])]
+    show_diff: bool,
+    /// How many unchanged lines to show around each change with `--show-diff`
+    #[clap(long, value_name = "N", requires = "show_diff")]
+    diff_context: Option<u32>,
+    /// Show how each submodule pointer differs between HEAD, the index and
+    /// the worktree, instead of the usual status
+    #[clap(long, conflicts_with_all = ["json", "terse", "show_diff"])]
+    compare_index_head: bool,
+    /// Only show dirty submodules
+    #[clap(long)]
+    dirty_only: bool,
+    /// List dirty submodules with the most changed files first, instead of
+    /// by name
+    #[clap(long)]
+    order_by_change_count: bool,
+    /// Check that the branch each submodule tracks exists on its remote.
+    /// This asks every remote for its branches, so it needs the network
+    #[clap(long, conflicts_with_all = ["json", "terse"])]
+    check_branches: bool,
+    /// Summarize the changes of each submodule on its line, e.g.
+    /// `3 modified, 1 untracked`, instead of listing every file
+    #[clap(long, conflicts_with_all = ["json", "terse", "show_diff"])]
+    compact_changes: bool,
+    /// Save the state of the submodules to this file, to compare against
+    /// later with `--diff-baseline`
+    #[clap(long, value_name = "FILE")]
+    record_baseline: Option<Utf8PathBuf>,
+    /// Show how the submodules changed since the state saved in this file
+    /// with `--record-baseline`
+    #[clap(long, value_name = "FILE", conflicts_with = "json")]
+    diff_baseline: Option<Utf8PathBuf>,
+    /// Exit with code 1 if anything changed since the baseline
+    #[clap(long, requires = "diff_baseline")]
+    exit_code: bool,
+}
+
+// Parses durations like `90s`, `30m`, `1h`, `2d` or `1w`.
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{}`", duration))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "unknown unit in `{}`, use s, m, h, d or w",
+                duration
+            ))
+        }
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration `{}` is too long", duration))
+}
+
+// Whether the changed file was modified after `cutoff`. Files that are gone
+// count as recent since there's no telling when they were removed.
+fn is_recent(work_dir: &Path, change: &Item, cutoff: SystemTime) -> bool {
+    std::fs::symlink_metadata(work_dir.join(gix::path::from_bstr(change.rela_path())))
+        .and_then(|metadata| metadata.modified())
+        .map_or(true, |modified| modified >= cutoff)
+}
+
+const TERSE_LEGEND: &str = ". clean  * dirty  ! conflict  _ uninitialized  ? unknown";
+
+// Describes the operation that's in progress in `repo`, if any.
+fn operation_in_progress(repo: &gix::Repository) -> Option<&'static str> {
+    use gix::state::InProgress;
+    Some(match repo.state()? {
+        InProgress::Merge => "merging",
+        InProgress::Rebase | InProgress::RebaseInteractive | InProgress::ApplyMailboxRebase => {
+            "rebasing"
+        }
+        InProgress::CherryPick | InProgress::CherryPickSequence => "cherry-picking",
+        InProgress::Revert | InProgress::RevertSequence => "reverting",
+        InProgress::Bisect => "bisecting",
+        InProgress::ApplyMailbox => "applying patches",
+    })
+}
+
+// Counts the changed, staged and untracked files in the superproject,
+// leaving out the submodules, which are listed separately.
+fn superproject_changes(repo: &gix::Repository) -> Result<usize, anyhow::Error> {
+    // A file that's both staged and changed again since is only counted once.
+    let mut changed = HashSet::new();
+    for item in repo
+        .status(gix::progress::Discard)?
+        .index_worktree_submodules(gix::status::Submodule::Given {
+            ignore: Ignore::All,
+            check_dirty: false,
+        })
+        .into_index_worktree_iter(Vec::new())?
+    {
+        let item = item?;
+        if item.summary().is_some() {
+            changed.insert(item.rela_path().to_owned());
+        }
+    }
+
+    // Then compare the index with `HEAD` for the staged changes. Without a
+    // commit yet, everything in the index is staged.
+    let index = repo.index_or_empty()?;
+    let head = match repo.head_commit() {
+        Ok(commit) => repo.index_from_tree(&commit.tree_id()?)?.into_parts().0,
+        Err(_) => gix::index::State::new(repo.object_hash()),
+    };
+    let is_file = |entry: &gix::index::Entry| entry.mode != gix::index::entry::Mode::COMMIT;
+    for entry in index.entries().iter().filter(|entry| is_file(entry)) {
+        let path = entry.path(&index);
+        let staged = match head.entry_by_path(path) {
+            Some(head_entry) => head_entry.id != entry.id || head_entry.mode != entry.mode,
+            None => true,
+        };
+        if staged {
+            changed.insert(path.to_owned());
+        }
+    }
+    for entry in head.entries().iter().filter(|entry| is_file(entry)) {
+        let path = entry.path(&head);
+        if index.entry_by_path(path).is_none() {
+            changed.insert(path.to_owned());
+        }
+    }
+
+    Ok(changed.len())
+}
+
+// Where a submodule's worktree really is, if it's reached through a symlink.
+// `toplevel` is the superproject's worktree with symlinks resolved.
+fn symlink_target(toplevel: &Path, path: &BStr) -> Option<PathBuf> {
+    let expected = toplevel.join(gix::path::from_bstr(path));
+    let resolved = expected.canonicalize().ok()?;
+    (resolved != expected).then_some(resolved)
+}
+
+// One column of `--compare-index-head` output, comparing the pointer on the
+// left with the one on the right: `.` for unchanged, `M` for moved, `+` for
+// added and `-` for removed.
+fn pointer_change(from: Option<gix::ObjectId>, to: Option<gix::ObjectId>) -> ColoredString {
+    match (from, to) {
+        (Some(from), Some(to)) if from == to => ".".dimmed(),
+        (None, None) => ".".dimmed(),
+        (Some(_), Some(_)) => "M".yellow().bold(),
+        (None, Some(_)) => "+".green().bold(),
+        (Some(_), None) => "-".red().bold(),
+    }
+}
+
+fn describe_pointer(id: Option<gix::ObjectId>) -> String {
+    match id {
+        Some(id) => id.to_hex_with_len(7).to_string(),
+        None => "none".to_string(),
+    }
+}
+
+// Prints the pointer of each submodule in the superproject's HEAD, in its
+// index and checked out in the worktree, with a two-column summary in front:
+// the first compares HEAD with the index, i.e. what's staged, the second the
+// index with the worktree, i.e. what isn't.
+fn print_pointer_states(
+    submodules: &[gix::Submodule<'_>],
+    format_path: &dyn Fn(&BStr) -> String,
+) -> Result<(), anyhow::Error> {
+    for submodule in submodules {
+        let head = submodule.head_id()?;
+        let index = submodule.index_id()?;
+        let worktree = match submodule.open()? {
+            Some(sm_repo) => sm_repo.head_id().ok().map(|id| id.detach()),
+            None => None,
+        };
+        println!(
+            "{}{} {} {} {}",
+            pointer_change(head, index),
+            pointer_change(index, worktree),
+            display_name(submodule)?,
+            format_path(submodule.path()?.as_ref()).as_str().dimmed(),
+            format!(
+                "(HEAD {}, index {}, worktree {})",
+                describe_pointer(head),
+                describe_pointer(index),
+                describe_pointer(worktree)
+            )
+            .dimmed()
+        );
+    }
+    Ok(())
+}
+
+// The branch `submodule` tracks according to `.gitmodules`, if any, with `.`
+// resolved to the superproject's current branch.
+fn tracked_branch(
+    repo: &gix::Repository,
+    submodule: &gix::Submodule<'_>,
+) -> Result<Option<BString>, anyhow::Error> {
+    Ok(match submodule.branch()? {
+        Some(Branch::Name(name)) => Some(name),
+        Some(Branch::CurrentInSuperproject) => {
+            repo.head_name()?.map(|name| name.shorten().to_owned())
+        }
+        None => None,
+    })
+}
+
+// Whether the remote at `url` has `branch`, asking it for its references.
+fn remote_has_branch(
+    repo: &gix::Repository,
+    url: &str,
+    branch: &BStr,
+) -> Result<bool, anyhow::Error> {
+    // gix runs `git upload-pack` for local remotes.
+    if gix::Url::try_from(url).is_ok_and(|url| url.scheme == gix::url::Scheme::File) {
+        crate::git_binary("checking the branches of a local remote")?;
+    }
+    // Branches are only listed if they're asked for.
+    let ref_map = repo
+        .remote_at(url)?
+        .with_refspecs(["refs/heads/*:refs/heads/*"], gix::remote::Direction::Fetch)?
+        .connect(gix::remote::Direction::Fetch)?
+        .ref_map(gix::progress::Discard, Default::default())?;
+    let mut expected = BString::from("refs/heads/");
+    expected.extend_from_slice(branch);
+    Ok(ref_map
+        .remote_refs
+        .iter()
+        .any(|remote_ref| remote_ref.unpack().0 == expected))
+}
+
+fn submodule_status(
+    submodule: &gix::Submodule<'_>,
+    ignore_filemode: bool,
+) -> Result<gix::submodule::Status, anyhow::Error> {
+    let mut status = submodule.status(Ignore::None, false)?;
+    if ignore_filemode {
+        if let Some(changes) = &mut status.changes {
+            changes.retain(|change| !is_mode_only(change));
+        }
+    }
+    Ok(status)
+}
+
+fn status_state(
+    submodule: &gix::Submodule<'_>,
+    status: &gix::submodule::Status,
+) -> Result<State, anyhow::Error> {
+    Ok(match status.is_dirty() {
+        Some(true) => State::Dirty,
+        Some(false) => State::Clean,
+        None if submodule.state()?.repository_exists => State::Unknown,
+        None => State::Uninitialized,
+    })
+}
+
+// The commits come from the superproject and the submodule rather than
+// `status`, since assumed-clean submodules and missing worktrees have none.
+fn status_entry(
+    submodule: &gix::Submodule<'_>,
+    url: Option<String>,
+    state: State,
+    operation: Option<&'static str>,
+    status: Option<&gix::submodule::Status>,
+    last_moved: Option<&history::PointerChange>,
+) -> Result<json::StatusEntry, anyhow::Error> {
+    Ok(json::StatusEntry {
+        name: submodule.name().to_str_lossy().into_owned(),
+        path: submodule.path()?.to_str_lossy().into_owned(),
+        url: url.unwrap_or_default(),
+        state,
+        operation,
+        recorded_commit: submodule.index_id()?.map(|id| id.to_string()),
+        checked_out_commit: submodule
+            .open()?
+            .and_then(|sm_repo| sm_repo.head_id().ok().map(|id| id.to_string())),
+        last_moved: last_moved.map(|change| json::LastMoved {
+            commit: change.commit.to_string(),
+            author: change.author.clone(),
+            date: change.time.format(gix::date::time::format::SHORT),
+        }),
+        changes: status
+            .and_then(|status| status.changes.as_deref())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(json::Change::from_item)
+            .collect(),
+    })
+}
+
+// Baselines are stored in the same form as the JSON output, but always cover
+// every submodule with its actual state, whichever ones are selected, shown
+// or assumed clean.
+fn baseline_entries(
+    repo: &gix::Repository,
+    cwd: &Utf8Path,
+    ignore_filemode: bool,
+) -> Result<Vec<json::StatusEntry>, anyhow::Error> {
+    let modules = url::raw_modules(repo)?;
+    let mut entries = Vec::new();
+    for submodule in select_submodules(repo, cwd, &[])? {
+        let url = url::declared_url(repo, modules.as_ref(), &submodule, UrlForm::Raw)?;
+        let (state, status) = match is_worktree_missing(&submodule)? {
+            true => (State::MissingWorktree, None),
+            false => {
+                let status = submodule_status(&submodule, ignore_filemode)?;
+                (status_state(&submodule, &status)?, Some(status))
+            }
+        };
+        let operation = match submodule.state()?.repository_exists {
+            true => submodule
+                .open()?
+                .and_then(|sm_repo| operation_in_progress(&sm_repo)),
+            false => None,
+        };
+        entries.push(status_entry(
+            &submodule,
+            url,
+            state,
+            operation,
+            status.as_ref(),
+            None,
+        )?);
+    }
+    Ok(entries)
+}
+
+// Counts the changes by kind, most pressing first, like
+// `1 conflict, 3 modified, 1 untracked`.
+fn compact_summary(changes: &[Item]) -> Option<String> {
+    const ORDER: [&str; 9] = [
+        "conflict",
+        "modified",
+        "removed",
+        "type-change",
+        "mode-change",
+        "renamed",
+        "copied",
+        "intent-to-add",
+        "untracked",
+    ];
+    let counts = changes
+        .iter()
+        .filter_map(json::Change::from_item)
+        .counts_by(|change| change.kind);
+    if counts.is_empty() {
+        return None;
+    }
+    let summary = ORDER
+        .iter()
+        .filter_map(|kind| {
+            let count = *counts.get(kind)?;
+            let label = match (*kind, count) {
+                ("conflict", 1) => "1 conflict".to_string(),
+                ("conflict", count) => format!("{} conflicts", count),
+                (kind, count) => format!("{} {}", count, kind),
+            };
+            Some(match *kind {
+                "conflict" => label.red().bold(),
+                "modified" | "removed" | "type-change" => label.yellow(),
+                _ => label.dimmed(),
+            })
+        })
+        .join(", ");
+    Some(summary)
+}
+
+fn describe_commit(commit: Option<&str>) -> &str {
+    match commit {
+        Some(commit) => &commit[..7.min(commit.len())],
+        None => "none",
+    }
+}
+
+// Prints how each submodule changed since `baseline`, returning whether
+// anything did.
+fn print_baseline_diff(baseline: &[json::BaselineEntry], entries: &[json::StatusEntry]) -> bool {
+    println!("{}", "since the baseline:".bold());
+    let mut changed = false;
+    for old in baseline {
+        if !entries.iter().any(|entry| entry.name == old.name) {
+            println!("  {} {}", old.name, "removed".red().bold());
+            changed = true;
+        }
+    }
+    for entry in entries {
+        let Some(old) = baseline.iter().find(|old| old.name == entry.name) else {
+            println!("  {} {}", entry.name, "added".green().bold());
+            changed = true;
+            continue;
+        };
+        let mut differences = Vec::new();
+        if old.recorded_commit != entry.recorded_commit {
+            differences.push(format!(
+                "bumped {} -> {}",
+                describe_commit(old.recorded_commit.as_deref()),
+                describe_commit(entry.recorded_commit.as_deref())
+            ));
+        }
+        if old.checked_out_commit != entry.checked_out_commit {
+            differences.push(format!(
+                "checked out {} -> {}",
+                describe_commit(old.checked_out_commit.as_deref()),
+                describe_commit(entry.checked_out_commit.as_deref())
+            ));
+        }
+        if old.state != entry.state {
+            differences.push(format!(
+                "{} -> {}",
+                state_label(old.state),
+                state_label(entry.state)
+            ));
+        }
+        if !differences.is_empty() {
+            println!("  {} {}", entry.name, differences.join(", "));
+            changed = true;
+        }
+    }
+    if !changed {
+        println!("  {}", "nothing changed".dimmed());
+    }
+    changed
+}
+
+fn has_conflicts(changes: &[Item]) -> bool {
+    changes.iter().any(|change| {
+        matches!(
+            change,
+            Item::Modification {
+                status: EntryStatus::Conflict(_),
+                ..
+            }
+        )
+    })
+}
+
+fn terse_symbol(state: State, conflicted: bool) -> ColoredString {
+    if conflicted {
+        return "!".red().bold();
+    }
+    match state {
+        State::Clean => ".".green(),
+        State::Dirty => "*".yellow().bold(),
+        State::Unknown | State::MissingWorktree => "?".bold(),
+        State::Uninitialized => "_".dimmed(),
+        State::AssumedClean => ".".dimmed(),
+    }
+}
+
+fn state_label(state: State) -> ColoredString {
+    match state {
+        State::Clean => "clean".green().bold(),
+        State::Dirty => "dirty".yellow().bold(),
+        State::Unknown => "unknown".bold(),
+        State::Uninitialized => "uninitialized".dimmed().bold(),
+        State::MissingWorktree => "moved/missing worktree".red().bold(),
+        State::AssumedClean => "assumed-clean".dimmed(),
+    }
+}
+
+pub fn status(cwd: &Utf8Path, args: StatusArgs) -> Result<(), anyhow::Error> {
+    let repo = crate::discover(cwd)?;
+    if repo.submodules()?.is_none() {
+        if args.json {
+            return json::print(Vec::<json::StatusEntry>::new(), args.schema_version);
+        }
+        println!("No submodules found");
+        return Ok(());
+    }
+    let mut submodules = select_submodules(&repo, cwd, &args.paths)?;
+
+    if !args.only.is_empty() {
+        let names = repo
+            .submodules()?
+            .into_iter()
+            .flatten()
+            .map(|submodule| submodule.name().to_owned())
+            .collect::<Vec<_>>();
+        if let Some(missing) = args
+            .only
+            .iter()
+            .find(|name| !names.iter().any(|known| known == name.as_str()))
+        {
+            anyhow::bail!("no submodule named {}", missing);
+        }
+        submodules.retain(|submodule| {
+            args.only
+                .iter()
+                .any(|name| submodule.name() == name.as_str())
+        });
+    }
+
+    let blamed = if args.blame_pointer {
+        let paths = submodules
+            .iter()
+            .map(|submodule| Ok(submodule.path()?.into_owned()))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        history::blame_pointers(&repo, &paths, history::MAX_BLAME_COMMITS)?
+    } else {
+        HashMap::new()
+    };
+
+    if !args.json && !args.terse {
+        if !args.no_superproject && repo.work_dir().is_some() {
+            match superproject_changes(&repo)? {
+                0 => println!("{} {}", "superproject".bold(), "clean".green().bold()),
+                1 => println!(
+                    "{} {} {}",
+                    "superproject".bold(),
+                    "dirty".yellow().bold(),
+                    "(1 changed file)".dimmed()
+                ),
+                count => println!(
+                    "{} {} {}",
+                    "superproject".bold(),
+                    "dirty".yellow().bold(),
+                    format!("({} changed files)", count).dimmed()
+                ),
+            }
+        }
+        if let Some(operation) = operation_in_progress(&repo) {
+            println!("{} {}", "superproject is".bold(), operation.red().bold());
+        }
+    }
+
+    // Paths are shown relative to the repository root, or to `cwd` with
+    // `--relative`. JSON output always uses paths relative to the root.
+    let relative = args.relative
+        || repo
+            .config_snapshot()
+            .boolean("gm.relativePaths")
+            .unwrap_or(false);
+    let relative_base = match repo.work_dir() {
+        Some(work_dir) if relative => {
+            Some((absolute_path(work_dir)?, absolute_path(cwd.as_std_path())?))
+        }
+        _ => None,
+    };
+    let format_path = |path: &BStr| match &relative_base {
+        Some((work_dir, cwd)) => relative_to(&work_dir.join(gix::path::from_bstr(path)), cwd)
+            .display()
+            .to_string(),
+        None => path.to_str_lossy().into_owned(),
+    };
+
+    if args.compare_index_head {
+        return print_pointer_states(&submodules, &format_path);
+    }
+
+    let assumed_clean = match args.assume_clean.is_empty() {
+        true => Vec::new(),
+        false => select_submodules(&repo, cwd, &args.assume_clean)?
+            .iter()
+            .map(|submodule| submodule.name().to_owned())
+            .collect(),
+    };
+
+    // Switching branches in the superproject doesn't move submodules, which
+    // leaves them looking dirty for no obvious reason.
+    if !args.json && !args.terse {
+        let mut moved = Vec::new();
+        for submodule in &submodules {
+            if assumed_clean.iter().any(|name| name == submodule.name()) {
+                continue;
+            }
+            let Some(sm_repo) = submodule.open()? else {
+                continue;
+            };
+            let (Some(recorded), Ok(head)) = (submodule.index_id()?, sm_repo.head_id()) else {
+                continue;
+            };
+            if head != recorded {
+                moved.push(display_name(submodule)?);
+            }
+        }
+        if !moved.is_empty() {
+            println!(
+                "{} {}",
+                "not at the recorded commit:".yellow().bold(),
+                moved.iter().join(", ")
+            );
+            println!(
+                "  {}",
+                "run `gm update` to check out the commits recorded in the superproject".dimmed()
+            );
+        }
+    }
+
+    // Sorting needs the changes of every submodule up front. They're kept
+    // so they don't have to be collected again.
+    let mut statuses = HashMap::new();
+    if args.order_by_change_count {
+        for submodule in &submodules {
+            if assumed_clean.iter().any(|name| name == submodule.name())
+                || (args.detect_moved_dirs && is_worktree_missing(submodule)?)
+            {
+                continue;
+            }
+            let status = submodule_status(submodule, args.ignore_filemode)?;
+            statuses.insert(submodule.name().to_owned(), status);
+        }
+        let change_count = |submodule: &gix::Submodule<'_>| {
+            statuses
+                .get(submodule.name())
+                .and_then(|status| status.changes.as_ref())
+                .map_or(0, |changes| changes.len())
+        };
+        submodules.sort_by_key(|submodule| std::cmp::Reverse(change_count(submodule)));
+    }
+
+    let modules = url::raw_modules(&repo)?;
+    let toplevel = repo.work_dir().map(absolute_path).transpose()?;
+    let mut entries = Vec::new();
+    let mut terse_line = Vec::new();
+    let mut uninitialized = Vec::new();
+    let mut conflicted = Vec::new();
+    for submodule in submodules {
+        let path = submodule.path()?.into_owned();
+        let last_moved = blamed.get(&path);
+        // The URL is only looked up when it's shown or serialized, so that a
+        // submodule without one doesn't get in the way of the rest.
+        let url = match args.json || args.emit_relative_urls.is_some() {
+            true => url::declared_url(
+                &repo,
+                modules.as_ref(),
+                &submodule,
+                args.emit_relative_urls.unwrap_or(UrlForm::Raw),
+            )?,
+            false => None,
+        };
+
+        let is_assumed_clean = assumed_clean.iter().any(|name| name == submodule.name());
+        let (state, status) = if is_assumed_clean {
+            // Nothing about the submodule is shown, but unless `--strict` is
+            // passed, the exit codes still reflect its actual state.
+            if !args.strict {
+                if !submodule.state()?.repository_exists {
+                    uninitialized.push(display_name(&submodule)?);
+                }
+                let status = submodule_status(&submodule, args.ignore_filemode)?;
+                if status.changes.as_deref().is_some_and(has_conflicts) {
+                    conflicted.push(display_name(&submodule)?);
+                }
+            }
+            (State::AssumedClean, None)
+        } else if args.detect_moved_dirs && is_worktree_missing(&submodule)? {
+            (State::MissingWorktree, None)
+        } else {
+            let status = match statuses.remove(submodule.name()) {
+                Some(status) => status,
+                None => submodule_status(&submodule, args.ignore_filemode)?,
+            };
+            (status_state(&submodule, &status)?, Some(status))
+        };
+        if state == State::Uninitialized {
+            uninitialized.push(display_name(&submodule)?);
+        }
+        // A submodule in the middle of a merge or rebase needs attention
+        // before anything else, so it's called out instead of just "dirty".
+        let operation = match submodule.state()?.repository_exists && !is_assumed_clean {
+            true => submodule
+                .open()?
+                .and_then(|sm_repo| operation_in_progress(&sm_repo)),
+            false => None,
+        };
+
+        let has_conflicts = status
+            .as_ref()
+            .and_then(|status| status.changes.as_deref())
+            .is_some_and(has_conflicts);
+        if has_conflicts {
+            conflicted.push(display_name(&submodule)?);
+        }
+
+        if args.json {
+            entries.push(status_entry(
+                &submodule,
+                url.clone(),
+                state,
+                operation,
+                status.as_ref(),
+                last_moved,
+            )?);
+        }
+
+        if args.dirty_only && state != State::Dirty {
+            continue;
+        }
+
+        if args.terse {
+            terse_line.push(terse_symbol(
+                state,
+                has_conflicts || operation == Some("merging"),
+            ));
+            continue;
+        }
+
+        if args.json {
+            continue;
+        }
+
+        let mut status = status;
+        let mut older = 0;
+        if let (Some(window), Some(changes)) = (
+            args.changed_since,
+            status.as_mut().and_then(|status| status.changes.as_mut()),
+        ) {
+            let work_dir = submodule.work_dir()?;
+            // A window reaching back further than the clock can go keeps
+            // every change.
+            let cutoff = SystemTime::now()
+                .checked_sub(window)
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            changes.retain(|change| {
+                let keep = change.summary().is_none() || is_recent(&work_dir, change, cutoff);
+                if !keep {
+                    older += 1;
+                }
+                keep
+            });
+        }
+
+        print!(
+            "{} {}",
+            display_name(&submodule)?,
+            format_path(path.as_ref()).as_str().dimmed()
+        );
+        if let Some(target) = toplevel
+            .as_deref()
+            .and_then(|toplevel| symlink_target(toplevel, path.as_ref()))
+        {
+            print!(" {}", format!("(symlink to {})", target.display()).dimmed());
+        }
+        if args.emit_relative_urls.is_some() {
+            print!(" {}", url.as_deref().unwrap_or("(no url)").dimmed());
+        }
+        match operation {
+            Some(operation) => print!(" {}", operation.red().bold()),
+            None => print!(" {}", state_label(state)),
+        }
+        if args.blame_pointer {
+            match last_moved {
+                Some(change) => print!(
+                    " {}",
+                    format!(
+                        "moved in {} by {} on {}",
+                        change.commit.to_hex_with_len(7),
+                        change.author,
+                        change.time.format(gix::date::time::format::SHORT)
+                    )
+                    .dimmed()
+                ),
+                None => print!(" {}", "last move not found".dimmed()),
+            }
+        }
+        if args.compact_changes {
+            if let Some(summary) = status
+                .as_ref()
+                .and_then(|status| status.changes.as_deref())
+                .and_then(compact_summary)
+            {
+                print!(" {}", summary);
+            }
+        }
+        println!();
+
+        if args.check_branches {
+            if let Some(branch) = tracked_branch(&repo, &submodule)? {
+                let resolved =
+                    url::submodule_url(&repo, modules.as_ref(), &submodule, UrlForm::Resolved)?;
+                match remote_has_branch(&repo, &resolved, branch.as_ref()) {
+                    Ok(true) => {}
+                    Ok(false) => println!(
+                        "  {} {}",
+                        format!("tracks missing branch {}", branch).red().bold(),
+                        format!("(not found on {})", resolved).dimmed()
+                    ),
+                    Err(err) => println!(
+                        "  {}",
+                        format!("couldn't check branch {}: {}", branch, err).dimmed()
+                    ),
+                }
+            }
+        }
+
+        if state == State::MissingWorktree {
+            println!(
+                "  {}",
+                "if the directory was moved, move it back and use `git mv`, or run `gm init` to check it out again"
+                    .dimmed()
+            );
+        }
+
+        if let Some(changes) = status
+            .and_then(|status| status.changes)
+            .filter(|_| !args.compact_changes)
+        {
+            if !changes.is_empty() {
+                println!("  changes:");
+            }
+
+            let sm_repo = match args.show_diff {
+                true => submodule.open()?,
+                false => None,
+            };
+            for change in changes {
+                display_change(&change, &|change_path| {
+                    if relative_base.is_some() {
+                        let mut full_path = path.clone();
+                        full_path.push(b'/');
+                        full_path.extend_from_slice(change_path);
+                        format_path(full_path.as_ref())
+                    } else {
+                        change_path.to_str_lossy().into_owned()
+                    }
+                })?;
+                if let Some(sm_repo) = &sm_repo {
+                    diff::print_change_diff(
+                        sm_repo,
+                        &change,
+                        args.diff_context.unwrap_or(diff::DEFAULT_CONTEXT),
+                    )?;
+                }
+            }
+        }
+        match older {
+            0 => {}
+            1 => println!("  {}", "1 older change not shown".dimmed()),
+            older => println!(
+                "  {}",
+                format!("{} older changes not shown", older).dimmed()
+            ),
+        }
+    }
+
+    if args.json {
+        let shown = entries
+            .iter()
+            .filter(|entry| !args.dirty_only || entry.state == State::Dirty)
+            .collect::<Vec<_>>();
+        json::print(shown, args.schema_version)?;
+    }
+
+    if args.terse {
+        println!("{}", terse_line.iter().join(""));
+        if !args.no_legend {
+            println!("{}", TERSE_LEGEND.dimmed());
+        }
+    }
+
+    // The old baseline is read before it's overwritten by the new one, which
+    // is written before exiting.
+    let baseline = match args.record_baseline.is_some() || args.diff_baseline.is_some() {
+        true => baseline_entries(&repo, cwd, args.ignore_filemode)?,
+        false => Vec::new(),
+    };
+    let changed = match &args.diff_baseline {
+        Some(old) => print_baseline_diff(&json::read_baseline(old)?, &baseline),
+        None => false,
+    };
+    if let Some(path) = &args.record_baseline {
+        json::write_baseline(path, &baseline)?;
+    }
+    if args.exit_code && changed {
+        std::process::exit(1);
+    }
+
+    if args.only_conflicts_exit_code && !conflicted.is_empty() {
+        if !args.json {
+            println!("{}", "conflicted submodules:".red().bold());
+            for name in conflicted {
+                println!("  {}", name);
+            }
+        }
+        std::process::exit(EXIT_CONFLICTS);
+    }
+
+    if args.fail_on_uninitialized && !uninitialized.is_empty() {
+        if !args.json {
+            println!("{}", "uninitialized submodules:".red().bold());
+            for name in uninitialized {
+                println!("  {}", name);
+            }
+        }
+        std::process::exit(EXIT_UNINITIALIZED);
+    }
+
+    Ok(())
+}
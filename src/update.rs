@@ -0,0 +1,514 @@
+use crate::{
+    discover, display_change, display_name, format_bytes, git_binary, parallel_map,
+    select_submodules, EXIT_CONFLICTS,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::{ColoredString, Colorize};
+use gix::status::index_worktree::iter::Item;
+use gix::status::plumbing::index_as_worktree::EntryStatus;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::BTreeMap;
+use std::io::{BufReader, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+#[derive(clap::Args)]
+pub struct UpdateArgs {
+    paths: Vec<Utf8PathBuf>,
+    /// Fetch shallowly when `.gitmodules` sets `shallow = true` (default)
+    #[clap(long, overrides_with = "no_recommend_shallow")]
+    recommend_shallow: bool,
+    /// Always fetch full history, ignoring the `shallow` hint in `.gitmodules`
+    #[clap(long)]
+    no_recommend_shallow: bool,
+    /// Never fetch; fail for submodules whose recorded commit isn't
+    /// available locally
+    #[clap(long)]
+    no_fetch: bool,
+    /// Don't show progress, and pass `--quiet` to git
+    #[clap(long, short)]
+    quiet: bool,
+    /// Update up to this many submodules at a time. The results are still
+    /// printed in order
+    #[clap(long, short)]
+    jobs: Option<usize>,
+    /// Update to the latest commit of the branch each submodule tracks,
+    /// instead of the commit recorded in the superproject
+    #[clap(long)]
+    remote: bool,
+    /// Merge the new commit into the submodule's current branch instead of
+    /// checking it out
+    #[clap(long, conflicts_with = "rebase")]
+    merge: bool,
+    /// Rebase the submodule's current branch onto the new commit instead of
+    /// checking it out
+    #[clap(long)]
+    rebase: bool,
+}
+
+// What git reported receiving while fetching a submodule.
+#[derive(Default, Clone, Copy)]
+struct Transfer {
+    objects: u64,
+    bytes: u64,
+}
+
+impl Transfer {
+    // git leaves out the size of small transfers.
+    fn describe(self) -> String {
+        match self.bytes {
+            0 => format!("fetched {} objects", self.objects),
+            bytes => format!("fetched {} objects, {}", self.objects, format_bytes(bytes)),
+        }
+    }
+}
+
+// Parses a size like `1.20 MiB` from git's progress output.
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, unit) = size.trim().split_once(' ')?;
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit {
+        "bytes" | "byte" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
+
+// Parses a line of git's progress output, like
+// `Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s`, into the
+// phase, the progress within it and the bytes received so far.
+fn parse_progress(line: &str) -> Option<(&str, u64, u64, Option<u64>)> {
+    let (phase, rest) = line.split_once(':')?;
+    let (_, rest) = rest.split_once('(')?;
+    let (counts, rest) = rest.split_once(')')?;
+    let (done, total) = counts.split_once('/')?;
+    let bytes = rest
+        .strip_prefix(", ")
+        .and_then(|rest| parse_size(rest.split([',', '|']).next()?));
+    Some((phase.trim(), done.parse().ok()?, total.parse().ok()?, bytes))
+}
+
+// Runs `git submodule update` for one submodule, turning git's progress into
+// `bar`. Everything else git prints is returned, to be shown if it fails.
+fn run_with_progress(
+    command: &mut Command,
+    bar: &ProgressBar,
+) -> Result<(ExitStatus, Transfer, Vec<String>), anyhow::Error> {
+    let mut child = command
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let mut transfer = Transfer::default();
+    let mut messages = Vec::new();
+    let mut line = Vec::new();
+    let mut handle_line = |line: &[u8]| {
+        let line = String::from_utf8_lossy(line);
+        match parse_progress(&line) {
+            Some((phase, done, total, bytes)) => {
+                bar.set_length(total);
+                bar.set_position(done);
+                bar.set_message(phase.to_string());
+                if phase == "Receiving objects" {
+                    transfer.objects = done;
+                    transfer.bytes = bytes.unwrap_or(transfer.bytes);
+                }
+            }
+            None if !line.trim().is_empty() => messages.push(line.into_owned()),
+            None => {}
+        }
+    };
+    // git redraws progress lines with `\r`.
+    for byte in BufReader::new(stderr).bytes() {
+        match byte? {
+            b'\r' | b'\n' => {
+                handle_line(&line);
+                line.clear();
+            }
+            byte => line.push(byte),
+        }
+    }
+    handle_line(&line);
+
+    Ok((child.wait()?, transfer, messages))
+}
+
+// A submodule to update, with everything the worker thread needs since
+// `gix::Submodule` can't be sent across.
+struct Target {
+    name: ColoredString,
+    path: PathBuf,
+    work_dir: PathBuf,
+    // Why the submodule can't be updated, if it can't.
+    skip: Option<&'static str>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Updated,
+    Failed,
+    // A merge or rebase stopped with conflicts in the submodule.
+    Conflicted,
+    // Not started because another submodule ran into conflicts.
+    Skipped,
+}
+
+// What updating a submodule printed, and how it went.
+struct Outcome {
+    lines: Vec<String>,
+    status: Status,
+    transfer: Transfer,
+}
+
+// The files with conflicts in the worktree at `work_dir`.
+fn conflicted_files(work_dir: &Path) -> Result<Vec<Item>, anyhow::Error> {
+    let repo = gix::open(work_dir)?;
+    let mut conflicts = Vec::new();
+    for item in repo
+        .status(gix::progress::Discard)?
+        .into_index_worktree_iter(Vec::new())?
+    {
+        let item = item?;
+        if matches!(
+            item,
+            Item::Modification {
+                status: EntryStatus::Conflict(_),
+                ..
+            }
+        ) {
+            conflicts.push(item);
+        }
+    }
+    Ok(conflicts)
+}
+
+// Prints the lines of each submodule in the order the submodules were
+// selected, holding back the ones that finish before those ahead of them.
+struct OrderedOutput<'a> {
+    // The index of the next submodule to print and the ones waiting for it.
+    pending: Mutex<(usize, BTreeMap<usize, Vec<String>>)>,
+    println: &'a (dyn Fn(String) -> Result<(), anyhow::Error> + Sync),
+}
+
+impl OrderedOutput<'_> {
+    fn finish(&self, idx: usize, lines: Vec<String>) -> Result<(), anyhow::Error> {
+        let mut pending = self.pending.lock().unwrap();
+        let (next, waiting) = &mut *pending;
+        waiting.insert(idx, lines);
+        while let Some(lines) = waiting.remove(next) {
+            for line in lines {
+                (self.println)(line)?;
+            }
+            *next += 1;
+        }
+        Ok(())
+    }
+}
+
+fn update_one(
+    git: &Path,
+    toplevel: &Path,
+    target: &Target,
+    args: &UpdateArgs,
+    bar: Option<&ProgressBar>,
+    capture: bool,
+) -> Result<Outcome, anyhow::Error> {
+    if let Some(reason) = target.skip {
+        return Ok(Outcome {
+            lines: vec![format!(
+                "{} {} {}",
+                "cannot update".red().bold(),
+                target.name,
+                reason.dimmed()
+            )],
+            status: Status::Failed,
+            transfer: Transfer::default(),
+        });
+    }
+
+    let mut command = Command::new(git);
+    command
+        .arg("submodule")
+        .arg("update")
+        .arg("--init")
+        .arg(if args.no_recommend_shallow {
+            "--no-recommend-shallow"
+        } else {
+            "--recommend-shallow"
+        });
+    if args.no_fetch {
+        command.arg("--no-fetch");
+    }
+    if args.remote {
+        command.arg("--remote");
+    }
+    if args.merge {
+        command.arg("--merge");
+    }
+    if args.rebase {
+        command.arg("--rebase");
+    }
+    if args.quiet {
+        command.arg("--quiet");
+    }
+    if bar.is_some() {
+        command.arg("--progress");
+    }
+    command.arg("--").arg(&target.path).current_dir(toplevel);
+
+    // git's own output is only passed through when nothing else could be
+    // printing at the same time.
+    let (status, transfer, messages) = match (bar, capture) {
+        (Some(bar), _) => run_with_progress(&mut command, bar)?,
+        (None, true) => run_with_progress(&mut command, &ProgressBar::hidden())?,
+        (None, false) => (command.spawn()?.wait()?, Transfer::default(), Vec::new()),
+    };
+    if !status.success() {
+        // The conflicts are listed at the end, so there's no need for git's
+        // messages about them.
+        if (args.merge || args.rebase) && !conflicted_files(&target.work_dir)?.is_empty() {
+            return Ok(Outcome {
+                lines: vec![format!("{} {}", "conflicts in".red().bold(), target.name)],
+                status: Status::Conflicted,
+                transfer,
+            });
+        }
+        let mut lines = vec![format!(
+            "{} {}",
+            "failed to update".red().bold(),
+            target.name
+        )];
+        lines.extend(
+            messages
+                .into_iter()
+                .map(|message| format!("  {}", message.dimmed())),
+        );
+        return Ok(Outcome {
+            lines,
+            status: Status::Failed,
+            transfer,
+        });
+    }
+
+    let is_shallow = gix::open(&target.work_dir).is_ok_and(|sm_repo| sm_repo.is_shallow());
+    let mut line = format!(
+        "{} {} {}",
+        "updated".bold(),
+        target.name,
+        if is_shallow {
+            "(shallow)".dimmed()
+        } else {
+            "(full history)".dimmed()
+        }
+    );
+    if transfer.objects > 0 {
+        line.push_str(&format!(" {}", transfer.describe().dimmed()));
+    }
+    Ok(Outcome {
+        lines: vec![line],
+        status: Status::Updated,
+        transfer,
+    })
+}
+
+pub fn update(cwd: &Utf8Path, args: UpdateArgs) -> Result<(), anyhow::Error> {
+    let git = git_binary("`gm update`")?;
+    let repo = discover(cwd)?;
+    let Some(toplevel) = repo.work_dir() else {
+        anyhow::bail!("cannot update submodules of a bare repository");
+    };
+
+    let mut targets = Vec::new();
+    for submodule in select_submodules(&repo, cwd, &args.paths)? {
+        // Cloning would need the network too, so with `--no-fetch` only
+        // submodules that already have the recorded commit can be updated.
+        let skip = match args.no_fetch {
+            true => match (submodule.open()?, submodule.index_id()?) {
+                (None, _) => Some("(not cloned)"),
+                (Some(_), None) => Some("(not in index)"),
+                (Some(sm_repo), Some(recorded)) if !sm_repo.has_object(recorded) => {
+                    Some("(recorded commit isn't available locally)")
+                }
+                _ => None,
+            },
+            false => None,
+        };
+        targets.push((
+            targets.len(),
+            Target {
+                name: display_name(&submodule)?,
+                path: gix::path::from_bstr(submodule.path()?).into_owned(),
+                work_dir: submodule.work_dir()?,
+                skip,
+            },
+        ));
+    }
+
+    // Registering a submodule writes to the superproject's `.git/config`,
+    // which parallel runs of `git submodule update --init` would fight over.
+    // Once they're all registered up front, there's nothing left to write.
+    let jobs = args.jobs.unwrap_or(1).max(1);
+    let paths = targets
+        .iter()
+        .filter(|(_, target)| target.skip.is_none())
+        .map(|(_, target)| &target.path)
+        .collect::<Vec<_>>();
+    if jobs > 1 && !paths.is_empty() {
+        let status = Command::new(&git)
+            .args(["submodule", "--quiet", "init", "--"])
+            .args(paths)
+            .current_dir(toplevel)
+            .status()?;
+        if !status.success() {
+            anyhow::bail!("failed to register the submodules");
+        }
+    }
+
+    // Progress bars only make sense on a terminal. While they're shown,
+    // everything else is printed through `multi` so it doesn't garble them.
+    let show_progress = !args.quiet && std::io::stderr().is_terminal();
+    let multi = MultiProgress::new();
+    let overall = match show_progress {
+        true => {
+            let overall = multi.add(ProgressBar::new(targets.len() as u64));
+            overall.set_style(ProgressStyle::with_template(
+                "{prefix:.bold} [{bar:30}] {pos}/{len}",
+            )?);
+            overall.set_prefix("updating");
+            Some(overall)
+        }
+        false => None,
+    };
+    let println = |line: String| match &overall {
+        Some(_) => multi.println(line).map_err(anyhow::Error::from),
+        None => {
+            println!("{}", line);
+            Ok(())
+        }
+    };
+    let output = OrderedOutput {
+        pending: Mutex::new((0, BTreeMap::new())),
+        println: &println,
+    };
+    let bar_style = ProgressStyle::with_template("{prefix} {msg:.dimmed} [{bar:30}] {pos}/{len}")?;
+
+    // Once a submodule has conflicts, the ones that haven't started yet are
+    // left alone so there's only one thing to sort out at a time.
+    let stop = AtomicBool::new(false);
+    let outcomes = parallel_map(&targets, Some(jobs), |(idx, target)| {
+        if stop.load(Ordering::Relaxed) {
+            let outcome = Outcome {
+                lines: vec![format!(
+                    "{} {} {}",
+                    "skipped".yellow().bold(),
+                    target.name,
+                    "(stopped after conflicts)".dimmed()
+                )],
+                status: Status::Skipped,
+                transfer: Transfer::default(),
+            };
+            output.finish(*idx, outcome.lines.clone())?;
+            return Ok(outcome);
+        }
+        let bar = overall.as_ref().map(|overall| {
+            let bar = multi.insert_before(overall, ProgressBar::new(0));
+            bar.set_style(bar_style.clone());
+            bar.set_prefix(target.name.to_string());
+            bar
+        });
+        // A submodule that couldn't even be attempted still needs its turn in
+        // the output, or everything after it would wait for it forever.
+        let outcome = update_one(&git, toplevel, target, &args, bar.as_ref(), jobs > 1)
+            .unwrap_or_else(|err| Outcome {
+                lines: vec![
+                    format!("{} {}", "failed to update".red().bold(), target.name),
+                    format!("  {}", err.to_string().dimmed()),
+                ],
+                status: Status::Failed,
+                transfer: Transfer::default(),
+            });
+        if outcome.status == Status::Conflicted {
+            stop.store(true, Ordering::Relaxed);
+        }
+        if let Some(bar) = bar {
+            bar.finish_and_clear();
+        }
+        if let Some(overall) = &overall {
+            overall.inc(1);
+        }
+        output.finish(*idx, outcome.lines.clone())?;
+        Ok::<_, anyhow::Error>(outcome)
+    });
+
+    let mut failed = Vec::new();
+    let mut conflicted = Vec::new();
+    let mut total = Transfer::default();
+    let mut fetched = 0;
+    for ((_, target), outcome) in targets.iter().zip(outcomes) {
+        let outcome = outcome?;
+        match outcome.status {
+            Status::Failed => failed.push(target.name.clone()),
+            Status::Conflicted => conflicted.push(target),
+            Status::Updated | Status::Skipped => {}
+        }
+        if outcome.transfer.objects > 0 {
+            fetched += 1;
+            total.objects += outcome.transfer.objects;
+            total.bytes += outcome.transfer.bytes;
+        }
+    }
+
+    if let Some(overall) = &overall {
+        overall.finish_and_clear();
+        if fetched > 0 {
+            println!(
+                "{} {}",
+                total.describe().bold(),
+                format!("for {} submodules", fetched).dimmed()
+            );
+        }
+    }
+
+    if !failed.is_empty() {
+        println!("{}", "failed to update:".red().bold());
+        for name in &failed {
+            println!("  {}", name);
+        }
+    }
+
+    for target in &conflicted {
+        println!("{} {}:", "conflicts in".red().bold(), target.name);
+        for change in conflicted_files(&target.work_dir)? {
+            display_change(&change, &|path| {
+                target
+                    .path
+                    .join(gix::path::from_bstr(path))
+                    .display()
+                    .to_string()
+            })?;
+        }
+        println!(
+            "  {}",
+            format!(
+                "resolve them in {} and finish the {} there, then run `gm update` again",
+                target.path.display(),
+                if args.rebase { "rebase" } else { "merge" }
+            )
+            .dimmed()
+        );
+    }
+    if !conflicted.is_empty() {
+        std::process::exit(EXIT_CONFLICTS);
+    }
+    if !failed.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
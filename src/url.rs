@@ -0,0 +1,213 @@
+use gix::bstr::ByteSlice;
+
+// Which form of a submodule's URL to show.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UrlForm {
+    /// The URL exactly as written in `.gitmodules`
+    Raw,
+    /// Relative URLs resolved against the superproject's remote
+    Resolved,
+}
+
+// Reads `.gitmodules` from the worktree, or from `HEAD` if it isn't checked
+// out, without applying the overrides from `.git/config`. That way the URLs
+// are exactly as written, which `Submodule::url` doesn't guarantee once a
+// submodule has been initialized.
+pub fn raw_modules(repo: &gix::Repository) -> Result<Option<gix::submodule::File>, anyhow::Error> {
+    let on_disk = repo
+        .work_dir()
+        .map(|work_dir| work_dir.join(".gitmodules"))
+        .filter(|path| path.is_file());
+    let bytes = match on_disk {
+        Some(path) => std::fs::read(path)?,
+        None => {
+            let Ok(commit) = repo.head_commit() else {
+                return Ok(None);
+            };
+            let mut tree = commit.tree()?;
+            let Some(entry) = tree.peel_to_entry_by_path(".gitmodules")? else {
+                return Ok(None);
+            };
+            entry.object()?.detach().data
+        }
+    };
+
+    Ok(Some(gix::submodule::File::from_bytes(
+        &bytes,
+        None,
+        &gix::config::File::default(),
+    )?))
+}
+
+// Relative submodule URLs are the ones git resolves against the
+// superproject's remote.
+pub fn is_relative(url: &str) -> bool {
+    url.starts_with("./") || url.starts_with("../")
+}
+
+// Resolves a relative submodule URL against `base` the way `git submodule`
+// does: each leading `../` strips one component off the base.
+pub fn resolve_relative(base: &str, url: &str) -> String {
+    let mut base = base.trim_end_matches('/').to_string();
+    let mut url = url;
+    let mut separator = '/';
+    loop {
+        if let Some(rest) = url.strip_prefix("./") {
+            url = rest;
+        } else if let Some(rest) = url.strip_prefix("../") {
+            url = rest;
+            // scp-like URLs (`host:path`) use a colon before the first component.
+            match base.rfind(['/', ':']) {
+                Some(idx) => {
+                    separator = if base[idx..].starts_with(':') {
+                        ':'
+                    } else {
+                        '/'
+                    };
+                    base.truncate(idx);
+                }
+                None => base.clear(),
+            }
+        } else {
+            break;
+        }
+    }
+
+    if base.is_empty() {
+        url.to_string()
+    } else {
+        format!("{}{}{}", base, separator, url)
+    }
+}
+
+// The URL relative submodule URLs are resolved against: the superproject's
+// default remote, or its worktree if it has no remote.
+pub fn resolution_base(repo: &gix::Repository) -> Result<String, anyhow::Error> {
+    if let Some(remote) = repo.find_default_remote(gix::remote::Direction::Fetch) {
+        if let Some(url) = remote?.url(gix::remote::Direction::Fetch) {
+            return Ok(url.to_bstring().to_str_lossy().into_owned());
+        }
+    }
+    match repo.work_dir() {
+        Some(work_dir) => Ok(crate::absolute_path(work_dir)?.display().to_string()),
+        None => Ok(crate::absolute_path(repo.git_dir())?.display().to_string()),
+    }
+}
+
+// Returns the submodule's URL in the requested form. `modules` is the result
+// of `raw_modules`; submodules not found in it fall back to the configured URL.
+pub fn submodule_url(
+    repo: &gix::Repository,
+    modules: Option<&gix::submodule::File>,
+    submodule: &gix::Submodule,
+    form: UrlForm,
+) -> Result<String, anyhow::Error> {
+    match declared_url(repo, modules, submodule, form)? {
+        Some(url) => Ok(url),
+        None => anyhow::bail!("{} has no url in .gitmodules", submodule.name()),
+    }
+}
+
+// Like `submodule_url`, but `None` for a submodule without a URL, for output
+// that can do without one.
+pub fn declared_url(
+    repo: &gix::Repository,
+    modules: Option<&gix::submodule::File>,
+    submodule: &gix::Submodule,
+    form: UrlForm,
+) -> Result<Option<String>, anyhow::Error> {
+    let raw = match modules.and_then(|modules| {
+        modules
+            .config()
+            .string(format!("submodule.{}.url", submodule.name()))
+    }) {
+        Some(url) => url.to_str_lossy().into_owned(),
+        None => match submodule.url() {
+            Ok(url) => url.to_bstring().to_str_lossy().into_owned(),
+            Err(gix::submodule::config::url::Error::Missing { .. }) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        },
+    };
+
+    Ok(Some(match form {
+        UrlForm::Resolved if is_relative(&raw) => resolve_relative(&resolution_base(repo)?, &raw),
+        _ => raw,
+    }))
+}
+
+// The ways of authenticating against a remote. A superproject and its
+// submodules using different ones is a common cause of failed clones, e.g.
+// when only https credentials or only an ssh key is set up.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Auth {
+    Https,
+    Ssh,
+}
+
+impl Auth {
+    pub fn of(url: &gix::Url) -> Option<Self> {
+        use gix::url::Scheme;
+        match url.scheme {
+            Scheme::Http | Scheme::Https => Some(Auth::Https),
+            Scheme::Ssh => Some(Auth::Ssh),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Auth::Https => "https",
+            Auth::Ssh => "ssh",
+        }
+    }
+
+    // The prefix of URLs on `host` using this kind of authentication, as used
+    // with `url.<base>.insteadOf`.
+    pub fn prefix(self, host: &str) -> String {
+        match self {
+            Auth::Https => format!("https://{}/", host),
+            Auth::Ssh => format!("git@{}:", host),
+        }
+    }
+}
+
+// A submodule whose absolute URL authenticates differently than the URL the
+// superproject was cloned from.
+pub struct AuthMismatch {
+    pub name: String,
+    pub url: String,
+    pub auth: Auth,
+    pub host: Option<String>,
+}
+
+// Finds the submodules in `modules` that don't use the `expected` kind of
+// authentication. Relative URLs always match.
+pub fn auth_mismatches(modules: &gix::submodule::File, expected: Auth) -> Vec<AuthMismatch> {
+    let mut mismatches = Vec::new();
+    for name in modules.names() {
+        let Some(url) = modules
+            .config()
+            .string(format!("submodule.{}.url", name))
+            .map(|url| url.to_str_lossy().into_owned())
+        else {
+            continue;
+        };
+        if is_relative(&url) {
+            continue;
+        }
+        let Ok(parsed) = gix::Url::try_from(url.as_str()) else {
+            continue;
+        };
+        match Auth::of(&parsed) {
+            Some(auth) if auth != expected => mismatches.push(AuthMismatch {
+                name: name.to_str_lossy().into_owned(),
+                host: parsed.host().map(str::to_owned),
+                url,
+                auth,
+            }),
+            _ => {}
+        }
+    }
+    mismatches.sort_by(|a, b| a.name.cmp(&b.name));
+    mismatches
+}
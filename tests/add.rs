@@ -0,0 +1,54 @@
+mod common;
+
+use common::{commit_file, git, gm, gm_ok, stderr, stdout, Fixture};
+
+#[test]
+fn add_force_re_adds_a_removed_submodule() {
+    let fixture = Fixture::new();
+    let root = fixture.repo("super");
+    let first = fixture.repo("first");
+    let second = fixture.repo("second");
+
+    gm_ok(&root, &["add", first.to_str().unwrap(), "lib"]);
+    git(&root, &["commit", "--quiet", "--message", "add lib"]);
+    gm_ok(&root, &["rm", "lib"]);
+    git(&root, &["commit", "--quiet", "--message", "remove lib"]);
+    assert!(root.join(".git/modules/lib").exists());
+
+    // git won't reuse the git directory left behind for another URL.
+    let output = gm(&root, &["add", second.to_str().unwrap(), "lib"]);
+    assert!(!output.status.success());
+
+    let output = gm_ok(&root, &["add", "--force", second.to_str().unwrap(), "lib"]);
+    assert!(output.contains("removed stale git directory"), "{}", output);
+    assert_eq!(
+        git(&root.join("lib"), &["remote", "get-url", "origin"]).trim(),
+        second.to_str().unwrap()
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("lib/README")).unwrap(),
+        "second"
+    );
+    assert!(git(&root, &["ls-files", "--stage", "lib"]).starts_with("160000"));
+}
+
+#[test]
+fn add_force_leaves_a_live_submodule_alone() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+    let other = fixture.repo("other");
+    commit_file(&root.join("liba"), "unpushed.txt", "unpushed");
+    let unpushed = git(&root.join("liba"), &["rev-parse", "HEAD"]);
+
+    let output = gm(&root, &["add", "--force", other.to_str().unwrap(), "liba"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("liba is still a submodule"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stdout(&output).contains("removed"), "{}", stdout(&output));
+    assert!(root.join(".git/modules/liba").is_dir());
+    assert_eq!(git(&root.join("liba"), &["rev-parse", "HEAD"]), unpushed);
+    assert!(git(&root, &["config", "--get", "submodule.liba.url"]).contains("upstream"));
+}
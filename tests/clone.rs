@@ -0,0 +1,95 @@
+mod common;
+
+use common::{gm_command, gm_ok, stderr, Fixture};
+
+// Clones the superproject with `args` and `env`, returning whether the
+// submodule was cloned too.
+fn clone_recurses(fixture: &Fixture, dest: &str, args: &[&str], env: &[(&str, &str)]) -> bool {
+    let source = fixture.path("super");
+    let mut command = gm_command(
+        &fixture.path(""),
+        &[&["clone", source.to_str().unwrap(), dest], args].concat(),
+    );
+    command.envs(env.iter().copied());
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    fixture.path(dest).join("liba/README").exists()
+}
+
+#[test]
+fn clone_recursion_sources() {
+    let fixture = Fixture::new();
+    fixture.superproject(&["liba"]);
+    let no_config_recurse = [
+        ("GIT_CONFIG_COUNT", "2"),
+        ("GIT_CONFIG_KEY_1", "submodule.recurse"),
+        ("GIT_CONFIG_VALUE_1", "false"),
+    ];
+
+    // Recursing is the default.
+    assert!(clone_recurses(&fixture, "default", &[], &[]));
+    assert!(!clone_recurses(&fixture, "flag", &["--no-recursive"], &[]));
+
+    // `GM_RECURSE` decides without a flag, and loses to one.
+    let env = [("GM_RECURSE", "false")];
+    assert!(!clone_recurses(&fixture, "env", &[], &env));
+    assert!(clone_recurses(&fixture, "env-flag", &["--recursive"], &env));
+
+    // `submodule.recurse` decides without either, and loses to both.
+    assert!(!clone_recurses(&fixture, "config", &[], &no_config_recurse));
+    let env = [&no_config_recurse[..], &[("GM_RECURSE", "true")]].concat();
+    assert!(clone_recurses(&fixture, "config-env", &[], &env));
+    assert!(clone_recurses(
+        &fixture,
+        "config-flag",
+        &["--recurse-submodules"],
+        &no_config_recurse
+    ));
+}
+
+#[test]
+fn clone_rejects_an_invalid_gm_recurse() {
+    let fixture = Fixture::new();
+    let source = fixture.superproject(&["liba"]);
+    let output = gm_command(
+        &fixture.path(""),
+        &["clone", source.to_str().unwrap(), "dest"],
+    )
+    .env("GM_RECURSE", "maybe")
+    .output()
+    .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("GM_RECURSE must be true or false, not maybe"));
+}
+
+#[test]
+fn clone_with_a_separate_git_dir() {
+    let fixture = Fixture::new();
+    let source = fixture.superproject(&["liba", "vendor/libb"]);
+    let git_dir = fixture.path("dest.git");
+    let output = gm_command(
+        &fixture.path(""),
+        &[
+            "clone",
+            "--separate-git-dir",
+            git_dir.to_str().unwrap(),
+            source.to_str().unwrap(),
+            "dest",
+        ],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(fixture.path("dest/.git").is_file());
+    assert!(git_dir.join("modules/liba").is_dir());
+
+    let output = gm_ok(&fixture.path("dest"), &["ls"]);
+    assert!(
+        output.contains("liba") && output.contains("libb"),
+        "{}",
+        output
+    );
+    let output = gm_ok(&fixture.path("dest"), &["status"]);
+    assert!(output.contains("liba clean"), "{}", output);
+    assert!(output.contains("libb clean"), "{}", output);
+}
@@ -0,0 +1,153 @@
+// Helpers shared by the integration tests, which build throwaway repositories
+// with the git binary and run `gm` against them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+// Isolates git from the user's configuration, and lets submodules be cloned
+// from local paths.
+fn configure(command: &mut Command) -> &mut Command {
+    command
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_COUNT", "1")
+        .env("GIT_CONFIG_KEY_0", "protocol.file.allow")
+        .env("GIT_CONFIG_VALUE_0", "always")
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("NO_COLOR", "1")
+        .env_remove("GM_RECURSE")
+}
+
+pub struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Fixture {
+            dir: tempfile::tempdir().expect("failed to create a temporary directory"),
+        }
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.dir.path().join(path)
+    }
+
+    // Creates a repository at `path` with a single commit.
+    pub fn repo(&self, path: &str) -> PathBuf {
+        let dir = self.path(path);
+        std::fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "--quiet", "--initial-branch", "main"]);
+        std::fs::write(dir.join("README"), path).unwrap();
+        git(&dir, &["add", "README"]);
+        git(&dir, &["commit", "--quiet", "--message", "initial"]);
+        dir
+    }
+
+    // Creates a superproject at `super` with a submodule at each of `paths`,
+    // cloned from repositories made for them, all added in one commit.
+    pub fn superproject(&self, paths: &[&str]) -> PathBuf {
+        self.superproject_at("super", paths)
+    }
+
+    pub fn superproject_at(&self, root: &str, paths: &[&str]) -> PathBuf {
+        let dir = self.repo(root);
+        for path in paths {
+            let url = self.repo(&format!("upstream/{}/{}", root, path));
+            git(
+                &dir,
+                &["submodule", "--quiet", "add", url.to_str().unwrap(), path],
+            );
+        }
+        git(&dir, &["commit", "--quiet", "--message", "add submodules"]);
+        dir
+    }
+}
+
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = configure(&mut Command::new("git"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+pub fn gm_command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_gm"));
+    configure(&mut command).args(args).current_dir(dir);
+    command
+}
+
+pub fn gm(dir: &Path, args: &[&str]) -> Output {
+    gm_command(dir, args).output().unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+// Runs `gm` and returns its stdout, failing the test if it fails.
+pub fn gm_ok(dir: &Path, args: &[&str]) -> String {
+    let output = gm(dir, args);
+    assert!(
+        output.status.success(),
+        "gm {} failed: {}{}",
+        args.join(" "),
+        stdout(&output),
+        stderr(&output)
+    );
+    stdout(&output)
+}
+
+// Commits `contents` to `file` in the repository at `dir`.
+pub fn commit_file(dir: &Path, file: &str, contents: &str) {
+    std::fs::write(dir.join(file), contents).unwrap();
+    git(dir, &["add", file]);
+    git(dir, &["commit", "--quiet", "--message", file]);
+}
+
+// Leaves the repository at `dir` in the middle of a merge with a conflict
+// in `README`.
+pub fn start_conflicted_merge(dir: &Path) {
+    start_conflicted(dir, "merge");
+}
+
+// Leaves the repository at `dir` in the middle of a rebase with a conflict
+// in `README`.
+pub fn start_conflicted_rebase(dir: &Path) {
+    start_conflicted(dir, "rebase");
+}
+
+fn start_conflicted(dir: &Path, operation: &str) {
+    git(dir, &["checkout", "--quiet", "-b", "ours"]);
+    commit_file(dir, "README", "ours");
+    git(dir, &["checkout", "--quiet", "-b", "theirs", "HEAD~1"]);
+    commit_file(dir, "README", "theirs");
+    let status = configure(&mut Command::new("git"))
+        .args([operation, "--quiet", "ours"])
+        .current_dir(dir)
+        .output()
+        .unwrap()
+        .status;
+    assert!(
+        !status.success(),
+        "the {} was expected to conflict",
+        operation
+    );
+}
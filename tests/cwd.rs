@@ -0,0 +1,28 @@
+mod common;
+
+use common::{gm_ok, Fixture};
+
+#[test]
+fn cwd_can_be_a_file() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "vendor/libb"]);
+    std::fs::create_dir(root.join("src")).unwrap();
+    std::fs::write(root.join("src/main.c"), "").unwrap();
+
+    // The file's directory is used, so paths are found from there.
+    for file in ["README", "src/main.c"] {
+        let cwd = root.join(file);
+        let output = gm_ok(&fixture.path(""), &["--cwd", cwd.to_str().unwrap(), "ls"]);
+        assert!(
+            output.contains("liba") && output.contains("libb"),
+            "{}",
+            output
+        );
+    }
+    let cwd = root.join("src/main.c");
+    let output = gm_ok(
+        &fixture.path(""),
+        &["--cwd", cwd.to_str().unwrap(), "info", "../vendor/libb"],
+    );
+    assert!(output.contains("libb"), "{}", output);
+}
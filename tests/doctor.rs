@@ -0,0 +1,59 @@
+mod common;
+
+use common::{git, gm, gm_ok, stdout, Fixture};
+
+#[test]
+fn doctor_finds_no_problems() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "vendor/libb"]);
+    assert!(gm_ok(&root, &["doctor"]).contains("no problems found"));
+}
+
+#[test]
+fn doctor_finds_name_collisions() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["vendor/lib", "third_party/lib", "liba"]);
+
+    let output = gm(&root, &["doctor"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("problem: third_party/lib and vendor/lib are both shown as lib"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("found 1 problem\n"), "{}", stdout);
+}
+
+#[test]
+fn doctor_finds_path_mismatches() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+    git(
+        &root,
+        &[
+            "config",
+            "--file",
+            ".gitmodules",
+            "submodule.liba.path",
+            "moved",
+        ],
+    );
+
+    let output = gm(&root, &["doctor"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains(
+            "problem: liba is at moved according to .gitmodules, but the index has no submodule there"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("problem: the index has a submodule at liba that isn't in .gitmodules"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("found 2 problems\n"), "{}", stdout);
+}
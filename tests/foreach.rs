@@ -0,0 +1,87 @@
+mod common;
+
+use common::{git, gm, gm_ok, stdout, Fixture};
+
+// Fails in `b` and `d`, and succeeds everywhere else.
+const COMMAND: &str = "case $name in b|d) exit 3 ;; esac";
+
+#[test]
+fn foreach_reports_mixed_results() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["a", "b", "c", "d"]);
+
+    let output = gm(&root, &["foreach", COMMAND]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("2 succeeded, 2 failed\n"), "{}", stdout);
+    assert!(
+        stdout.contains("  b exited with 3\n  d exited with 3\n"),
+        "{}",
+        stdout
+    );
+    assert_eq!(stdout.matches("entering").count(), 4, "{}", stdout);
+}
+
+#[test]
+fn foreach_fail_fast_stops_at_the_first_failure() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["a", "b", "c", "d"]);
+
+    let output = gm(&root, &["foreach", "--fail-fast", COMMAND]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("1 succeeded, 1 failed (stopped at first failure)\n"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("entering c"), "{}", stdout);
+}
+
+#[test]
+fn foreach_ignore_errors_exits_successfully() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["a", "b", "c", "d"]);
+
+    let output = gm(&root, &["foreach", "--ignore-errors", COMMAND]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("2 succeeded, 2 failed\n"));
+
+    // Together, the run still stops early but doesn't fail.
+    let output = gm(
+        &root,
+        &["foreach", "--ignore-errors", "--fail-fast", COMMAND],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("1 succeeded, 1 failed"));
+}
+
+#[test]
+fn foreach_dirty_only_reports_what_it_skips() {
+    let fixture = Fixture::new();
+    fixture.superproject(&["a", "b", "c"]);
+    let clone = fixture.path("clone");
+    git(
+        &fixture.path(""),
+        &["clone", "--quiet", "super", clone.to_str().unwrap()],
+    );
+    git(
+        &clone,
+        &["submodule", "--quiet", "update", "--init", "a", "b"],
+    );
+    std::fs::write(clone.join("b/README"), "changed").unwrap();
+
+    let stdout = gm_ok(&clone, &["foreach", "--dirty-only", "true"]);
+    assert!(
+        stdout.contains("skipping clean submodules: a\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("skipping uninitialized submodules: c\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("entering b\n"), "{}", stdout);
+    assert!(stdout.contains("1 succeeded, 0 failed\n"), "{}", stdout);
+}
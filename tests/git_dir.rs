@@ -0,0 +1,44 @@
+mod common;
+
+use common::{gm_ok, Fixture};
+
+#[test]
+fn git_dir_and_work_tree_work_from_an_unrelated_directory() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "vendor/libb"]);
+    let elsewhere = fixture.path("elsewhere");
+    std::fs::create_dir(&elsewhere).unwrap();
+    let git_dir = root.join(".git");
+    let args = [
+        "--git-dir",
+        git_dir.to_str().unwrap(),
+        "--work-tree",
+        root.to_str().unwrap(),
+    ];
+
+    let output = gm_ok(&elsewhere, &[&args[..], &["ls"]].concat());
+    assert!(
+        output.contains("liba") && output.contains("libb"),
+        "{}",
+        output
+    );
+
+    let output = gm_ok(&elsewhere, &[&args[..], &["status"]].concat());
+    assert!(output.contains("superproject clean"), "{}", output);
+    assert!(output.contains("liba clean"), "{}", output);
+    assert!(output.contains("libb clean"), "{}", output);
+
+    // Paths are relative to the worktree, and commands run in the submodule
+    // see its own repository rather than the superproject's.
+    let output = gm_ok(
+        &elsewhere,
+        &[&args[..], &["foreach", "git rev-parse --show-toplevel"]].concat(),
+    );
+    assert!(
+        output.contains(root.join("vendor/libb").to_str().unwrap()),
+        "{}",
+        output
+    );
+    let output = gm_ok(&elsewhere, &[&args[..], &["info", "vendor/libb"]].concat());
+    assert!(output.contains("libb"), "{}", output);
+}
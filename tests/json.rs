@@ -0,0 +1,33 @@
+mod common;
+
+use common::{gm_ok, Fixture};
+use serde_json::Value;
+
+// Bumped along with `SCHEMA_VERSION` in src/json.rs.
+const SCHEMA_VERSION: u64 = 7;
+
+#[test]
+fn schema_version_is_included_on_request() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+
+    for args in [
+        &["ls", "--json", "--schema-version"][..],
+        &["status", "--json", "--schema-version"],
+        &["info", "liba", "--json", "--schema-version"],
+        &["export", "--format", "lockfile"],
+    ] {
+        let output: Value = serde_json::from_str(&gm_ok(&root, args)).unwrap();
+        assert_eq!(output["version"], SCHEMA_VERSION, "{:?}", args);
+        assert_eq!(
+            output["submodules"].as_array().unwrap().len(),
+            1,
+            "{:?}",
+            args
+        );
+    }
+
+    // Without the flag, the output is just the array.
+    let output: Value = serde_json::from_str(&gm_ok(&root, &["status", "--json"])).unwrap();
+    assert!(output.is_array());
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::{gm_ok, Fixture};
+
+#[test]
+fn repos_leaves_the_subcommand_arguments_alone() {
+    let fixture = Fixture::new();
+    fixture.superproject_at("fleet/one", &["liba"]);
+    fixture.superproject_at("fleet/two", &["libb"]);
+
+    let output = gm_ok(
+        &fixture.path(""),
+        &["--repos", "fleet", "foreach", "echo", "--repos", "x"],
+    );
+    assert!(
+        output.contains("one") && output.contains("two"),
+        "{}",
+        output
+    );
+    assert_eq!(output.matches("--repos x").count(), 2, "{}", output);
+    assert!(output.contains("ran in 2 repositories"), "{}", output);
+}
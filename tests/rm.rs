@@ -0,0 +1,55 @@
+mod common;
+
+use common::{git, gm, gm_ok, stderr, Fixture};
+
+#[test]
+fn rm_cleans_up_gitmodules_the_index_and_the_worktree() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["vendor/liba", "libb"]);
+
+    let output = gm_ok(&root, &["rm", "vendor/liba"]);
+    assert!(output.contains("removed liba vendor/liba"), "{}", output);
+
+    let gitmodules = std::fs::read_to_string(root.join(".gitmodules")).unwrap();
+    assert!(!gitmodules.contains("vendor/liba"), "{}", gitmodules);
+    assert!(gitmodules.contains("libb"), "{}", gitmodules);
+    assert!(!root.join("vendor").exists());
+    assert_eq!(git(&root, &["ls-files", "--stage", "vendor/liba"]), "");
+    assert!(!git(&root, &["config", "--list", "--local"]).contains("vendor/liba"));
+
+    // The removal is staged as a whole, including `.gitmodules`.
+    assert_eq!(
+        git(&root, &["status", "--porcelain"]),
+        "M  .gitmodules\nD  vendor/liba\n"
+    );
+}
+
+#[test]
+fn rm_cached_keeps_the_worktree() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+
+    gm_ok(&root, &["rm", "--cached", "liba"]);
+    assert!(root.join("liba/README").exists());
+    assert_eq!(git(&root, &["ls-files", "--stage", "liba"]), "");
+    assert!(!std::fs::read_to_string(root.join(".gitmodules"))
+        .unwrap()
+        .contains("liba"));
+}
+
+#[test]
+fn rm_refuses_to_drop_local_changes() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+    std::fs::write(root.join("liba/README"), "changed").unwrap();
+
+    let output = gm(&root, &["rm", "liba"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("has local changes"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(root.join("liba/README").exists());
+    assert!(git(&root, &["ls-files", "--stage", "liba"]).starts_with("160000"));
+}
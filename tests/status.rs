@@ -0,0 +1,360 @@
+mod common;
+
+use common::{
+    commit_file, git, gm, gm_ok, start_conflicted_merge, start_conflicted_rebase, stderr, stdout,
+    Fixture,
+};
+use serde_json::Value;
+use std::os::unix::fs::PermissionsExt;
+
+fn status_json(dir: &std::path::Path, args: &[&str]) -> Vec<Value> {
+    let output = gm_ok(dir, &[&["status", "--json"], args].concat());
+    serde_json::from_str(&output).unwrap()
+}
+
+#[test]
+fn blame_pointer_finds_submodules_sharing_a_directory() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["vendor/liba", "vendor/libb"]);
+    let added = git(&root, &["rev-parse", "HEAD"]);
+    std::fs::write(root.join("later.txt"), "later").unwrap();
+    git(&root, &["add", "later.txt"]);
+    git(&root, &["commit", "--quiet", "--message", "later"]);
+
+    let entries = status_json(&root, &["--blame-pointer"]);
+    assert_eq!(entries.len(), 2);
+    for entry in &entries {
+        assert_eq!(
+            entry["last_moved"]["commit"],
+            added.trim(),
+            "{}",
+            entry["path"]
+        );
+        let history: Vec<Value> = serde_json::from_str(&gm_ok(
+            &root,
+            &["list-commits", entry["path"].as_str().unwrap(), "--json"],
+        ))
+        .unwrap();
+        assert_eq!(
+            history.last().unwrap()["commit"],
+            entry["last_moved"]["commit"]
+        );
+    }
+}
+
+#[test]
+fn superproject_counts_staged_changes() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+    let first_line =
+        |root: &std::path::Path| gm_ok(root, &["status"]).lines().next().unwrap().to_string();
+    assert_eq!(first_line(&root), "superproject clean");
+
+    std::fs::write(root.join("README"), "changed").unwrap();
+    git(&root, &["add", "README"]);
+    assert_eq!(first_line(&root), "superproject dirty (1 changed file)");
+
+    // A file that's staged and then changed again, or removed from the
+    // index but left in the worktree, is only counted once.
+    std::fs::write(root.join("README"), "changed again").unwrap();
+    std::fs::write(root.join("new.txt"), "new").unwrap();
+    git(&root, &["add", "new.txt"]);
+    git(&root, &["rm", "--quiet", "--cached", ".gitmodules"]);
+    assert_eq!(first_line(&root), "superproject dirty (3 changed files)");
+}
+
+#[test]
+fn assume_clean_keeps_the_conflicts_exit_code_unless_strict() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "libb"]);
+    start_conflicted_merge(&root.join("liba"));
+
+    let output = gm(&root, &["status", "--only-conflicts-exit-code"]);
+    assert_eq!(output.status.code(), Some(4));
+
+    let output = gm(
+        &root,
+        &[
+            "status",
+            "--only-conflicts-exit-code",
+            "--assume-clean",
+            "liba",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stdout(&output).contains("liba assumed-clean"));
+
+    let output = gm(
+        &root,
+        &[
+            "status",
+            "--only-conflicts-exit-code",
+            "--assume-clean",
+            "liba",
+            "--strict",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn assume_clean_keeps_the_uninitialized_exit_code_unless_strict() {
+    let fixture = Fixture::new();
+    fixture.superproject(&["liba"]);
+    let root = fixture.path("clone");
+    git(
+        &fixture.path(""),
+        &["clone", "--quiet", "super", root.to_str().unwrap()],
+    );
+
+    let args = [
+        "status",
+        "--fail-on-uninitialized",
+        "--assume-clean",
+        "liba",
+    ];
+    assert_eq!(gm(&root, &args).status.code(), Some(3));
+    assert_eq!(
+        gm(&root, &[&args[..], &["--strict"]].concat())
+            .status
+            .code(),
+        Some(0)
+    );
+}
+
+#[test]
+fn baseline_covers_every_submodule() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "libb"]);
+    std::fs::write(root.join("liba/README"), "changed").unwrap();
+    let baseline = fixture.path("baseline.json");
+    let baseline = baseline.to_str().unwrap();
+
+    gm_ok(
+        &root,
+        &[
+            "status",
+            "--dirty-only",
+            "--assume-clean",
+            "libb",
+            "--record-baseline",
+            baseline,
+        ],
+    );
+    let recorded: Value =
+        serde_json::from_str(&std::fs::read_to_string(baseline).unwrap()).unwrap();
+    let submodules = recorded["submodules"].as_array().unwrap();
+    assert_eq!(submodules.len(), 2);
+    for submodule in submodules {
+        assert!(submodule["recorded_commit"].is_string());
+        assert!(submodule["checked_out_commit"].is_string());
+    }
+
+    let output = gm(
+        &root,
+        &[
+            "status",
+            "--diff-baseline",
+            baseline,
+            "--exit-code",
+            "--assume-clean",
+            "libb",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    assert!(stdout(&output).contains("nothing changed"));
+}
+
+#[test]
+fn diff_baseline_exit_code_still_records_the_new_baseline() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+    let baseline = fixture.path("baseline.json");
+    let baseline = baseline.to_str().unwrap();
+    gm_ok(&root, &["status", "--record-baseline", baseline]);
+
+    commit_file(&root.join("liba"), "README", "moved");
+    let args = [
+        "status",
+        "--diff-baseline",
+        baseline,
+        "--record-baseline",
+        baseline,
+        "--exit-code",
+    ];
+    let output = gm(&root, &args);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("liba checked out"));
+    assert_eq!(gm(&root, &args).status.code(), Some(0));
+}
+
+#[test]
+fn changed_since_rejects_durations_that_overflow() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+
+    let output = gm(&root, &["status", "--changed-since", "99999999999999999w"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("is too long"),
+        "{}",
+        stderr(&output)
+    );
+
+    // A window longer than the clock goes back keeps every change.
+    std::fs::write(root.join("liba/README"), "changed").unwrap();
+    let output = gm_ok(&root, &["status", "--changed-since", "30000000000000w"]);
+    assert!(output.contains("README"), "{}", output);
+}
+
+#[test]
+fn detect_moved_dirs_reports_a_renamed_worktree() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "libb"]);
+    std::fs::rename(root.join("liba"), root.join("liba-moved")).unwrap();
+
+    let output = gm_ok(&root, &["status", "--detect-moved-dirs"]);
+    assert!(output.contains("liba moved/missing worktree"), "{}", output);
+    assert!(output.contains("libb clean"), "{}", output);
+
+    let entries = status_json(&root, &["--detect-moved-dirs"]);
+    assert_eq!(entries[0]["state"], "missing-worktree");
+    assert_eq!(entries[1]["state"], "clean");
+}
+
+#[test]
+fn operations_in_progress_are_called_out() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "libb", "libc"]);
+    start_conflicted_merge(&root.join("liba"));
+    start_conflicted_rebase(&root.join("libb"));
+
+    let output = gm_ok(&root, &["status"]);
+    assert!(output.contains("liba merging"), "{}", output);
+    assert!(output.contains("libb rebasing"), "{}", output);
+    assert!(output.contains("libc clean"), "{}", output);
+    assert!(!output.contains("superproject is"), "{}", output);
+
+    let entries = status_json(&root, &[]);
+    assert_eq!(entries[0]["operation"], "merging");
+    assert_eq!(entries[1]["operation"], "rebasing");
+    assert!(entries[2]["operation"].is_null());
+
+    start_conflicted_merge(&root);
+    let output = gm_ok(&root, &["status"]);
+    assert!(output.contains("superproject is merging"), "{}", output);
+}
+
+#[test]
+fn mode_only_changes_are_labeled() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba"]);
+    let readme = root.join("liba/README");
+    std::fs::set_permissions(&readme, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = gm_ok(&root, &["status"]);
+    assert!(output.contains("liba dirty"), "{}", output);
+    assert!(output.contains("README mode"), "{}", output);
+
+    let entries = status_json(&root, &[]);
+    assert_eq!(entries[0]["changes"][0]["path"], "README");
+    assert_eq!(entries[0]["changes"][0]["kind"], "mode-change");
+
+    let output = gm_ok(&root, &["status", "--ignore-filemode"]);
+    assert!(output.contains("liba clean"), "{}", output);
+    assert!(!output.contains("README"), "{}", output);
+}
+
+#[test]
+fn symlinked_submodule_directories_are_followed() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "libb"]);
+    // The worktree stays at the same depth, so its `.git` file still points
+    // at the right git directory.
+    std::fs::rename(root.join("liba"), root.join("liba-real")).unwrap();
+    std::os::unix::fs::symlink("liba-real", root.join("liba")).unwrap();
+    let real = root.canonicalize().unwrap().join("liba-real");
+
+    let output = gm_ok(&root, &["status", "--detect-moved-dirs"]);
+    let line = output
+        .lines()
+        .find(|line| line.starts_with("liba "))
+        .unwrap();
+    assert_eq!(
+        line,
+        format!("liba liba (symlink to {}) clean", real.display()),
+        "{}",
+        output
+    );
+
+    // Paths through the symlink still select the submodule it leads to.
+    let output = gm_ok(&root, &["status", "--no-superproject", "liba/README"]);
+    assert!(output.contains("liba (symlink to"), "{}", output);
+    assert!(!output.contains("libb"), "{}", output);
+}
+
+#[test]
+fn status_works_without_urls() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "libb"]);
+    git(
+        &root,
+        &[
+            "config",
+            "--file",
+            ".gitmodules",
+            "--unset",
+            "submodule.libb.url",
+        ],
+    );
+    git(&root, &["config", "--unset", "submodule.libb.url"]);
+
+    let output = gm_ok(&root, &["status"]);
+    assert!(output.contains("libb clean"), "{}", output);
+
+    let entries = status_json(&root, &[]);
+    assert_eq!(entries[1]["url"], "");
+    assert!(entries[0]["url"].as_str().unwrap().contains("upstream"));
+
+    let output = gm_ok(&root, &["status", "--emit-relative-urls", "raw"]);
+    assert!(output.contains("libb (no url) clean"), "{}", output);
+}
+
+#[test]
+fn baseline_ignores_selection_and_assume_clean() {
+    let fixture = Fixture::new();
+    let root = fixture.superproject(&["liba", "libb"]);
+    let baseline = fixture.path("baseline.json");
+    let baseline = baseline.to_str().unwrap();
+    gm_ok(&root, &["status", "--record-baseline", baseline]);
+
+    for args in [
+        &["--assume-clean", "libb"][..],
+        &["liba"],
+        &["--only", "liba"],
+    ] {
+        let output = gm(
+            &root,
+            &[
+                &["status", "--diff-baseline", baseline, "--exit-code"],
+                args,
+            ]
+            .concat(),
+        );
+        assert_eq!(
+            output.status.code(),
+            Some(0),
+            "{:?}: {}",
+            args,
+            stdout(&output)
+        );
+        assert!(stdout(&output).contains("nothing changed"), "{:?}", args);
+    }
+
+    // Recording with a selection still covers every submodule.
+    gm_ok(&root, &["status", "liba", "--record-baseline", baseline]);
+    let recorded: Value =
+        serde_json::from_str(&std::fs::read_to_string(baseline).unwrap()).unwrap();
+    assert_eq!(recorded["submodules"].as_array().unwrap().len(), 2);
+    assert_eq!(recorded["submodules"][1]["state"], "clean");
+}
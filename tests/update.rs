@@ -0,0 +1,91 @@
+mod common;
+
+use common::{git, gm_command, Fixture};
+use std::os::unix::fs::PermissionsExt;
+
+// Puts a script that runs `script` before running git in front of `PATH`,
+// returning the new `PATH`.
+fn git_shim(fixture: &Fixture, script: &str) -> std::ffi::OsString {
+    let bin = fixture.path("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let shim = bin.join("git");
+    std::fs::write(
+        &shim,
+        format!(
+            "#!/bin/sh\n{}\nexec '{}' \"$@\"\n",
+            script,
+            which::which("git").unwrap().display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&shim, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::env::join_paths(
+        std::iter::once(bin).chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())),
+    )
+    .unwrap()
+}
+
+#[test]
+fn parallel_update_prints_in_a_stable_order() {
+    let fixture = Fixture::new();
+    fixture.superproject(&["a", "b", "c", "d"]);
+
+    // A stand-in for git that takes longer for some submodules than others,
+    // so they finish out of order.
+    let path = git_shim(
+        &fixture,
+        "case \"$*\" in\n  *' -- a') sleep 0.6 ;;\n  *' -- c') sleep 0.3 ;;\nesac",
+    );
+
+    let mut outputs = Vec::new();
+    for run in 0..3 {
+        let clone = fixture.path(&format!("clone{}", run));
+        git(
+            &fixture.path(""),
+            &["clone", "--quiet", "super", clone.to_str().unwrap()],
+        );
+        let output = gm_command(&clone, &["update", "-j", "4"])
+            .env("PATH", &path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        outputs.push(String::from_utf8(output.stdout).unwrap());
+    }
+
+    let names = outputs[0]
+        .lines()
+        .map(|line| line.split(' ').nth(1).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b", "c", "d"], "{}", outputs[0]);
+    assert!(
+        outputs.iter().all(|output| *output == outputs[0]),
+        "{:?}",
+        outputs
+    );
+}
+
+#[test]
+fn parallel_update_reports_submodules_it_could_not_start() {
+    let fixture = Fixture::new();
+    fixture.superproject(&["a", "b", "c", "d"]);
+    // Gone once the submodules are registered, so git can't be run for any
+    // of them.
+    let path = git_shim(&fixture, "case \"$*\" in\n  *' init '*) rm \"$0\" ;;\nesac");
+    let clone = fixture.path("clone");
+    git(
+        &fixture.path(""),
+        &["clone", "--quiet", "super", clone.to_str().unwrap()],
+    );
+
+    let output = gm_command(&clone, &["update", "-j", "4"])
+        .env("PATH", &path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let failures = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("failed to update "))
+        .collect::<Vec<_>>();
+    assert_eq!(failures, ["a", "b", "c", "d"], "{}", stdout);
+}